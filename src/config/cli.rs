@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// LSP Proxy to connect your local environment to Docker
 #[derive(Parser, Debug, Default)]
@@ -31,9 +31,36 @@ pub struct Cli {
     /// Log level: can be trace, debug, info, warning or error
     #[arg(short, long)]
     pub log_level: Option<String>,
+    /// Configuration file; replaces the project file search, it can be set with LSPDOCK_CONFIG
+    #[arg(long)]
+    pub config: Option<String>,
+    /// Wait for the `initialize` request and use its root as the project directory instead of
+    /// the current working directory
+    #[arg(long)]
+    pub lazy: bool,
     /// Arguments to pass to the LSP
     #[arg(last = true)]
     pub args: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Print the JSON Schema of lspdock.toml, for completion and validation in the editor
+    Schema,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the resolved configuration, where each field comes from and the command that
+    /// would be spawned, without starting the LSP
+    Show,
 }
 
 impl Cli {
@@ -93,6 +120,8 @@ impl Cli {
                         "--pattern",
                         "-l",
                         "--log-level",
+                        "--config",
+                        "--lazy",
                         "-h",
                         "--help",
                         "-V",
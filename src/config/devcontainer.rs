@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::ProxyConfigToml;
+
+const DEVCONTAINER_DIR: &str = ".devcontainer";
+const DEVCONTAINER_NAME: &str = "devcontainer.json";
+const HIDDEN_DEVCONTAINER_NAME: &str = ".devcontainer.json";
+
+/// The subset of `devcontainer.json` used by lspdock
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct DevContainer {
+    workspace_folder: Option<String>,
+    workspace_mount: Option<String>,
+    /// Compose service, when the dev container is defined by a compose file
+    service: Option<String>,
+    docker_compose_file: Option<ComposeFiles>,
+    remote_user: Option<String>,
+    container_user: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ComposeFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Search `.devcontainer/devcontainer.json` or `.devcontainer.json` from `start` upward,
+/// stopping at the git root or at the filesystem root
+pub(super) fn find_devcontainer(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        for candidate in [
+            dir.join(DEVCONTAINER_DIR).join(DEVCONTAINER_NAME),
+            dir.join(HIDDEN_DEVCONTAINER_NAME),
+        ] {
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Build a configuration layer from the content of a `devcontainer.json` file (JSON with
+/// comments). The local workspace folder is the directory that contains the file, or the
+/// `.devcontainer` directory.
+pub(super) fn parse_devcontainer(
+    content: &str,
+    path: &Path,
+) -> Result<ProxyConfigToml, json5::Error> {
+    let devcontainer: DevContainer = json5::from_str(content)?;
+
+    let file_dir = path.parent().unwrap_or(Path::new("/"));
+    let workspace = if file_dir.file_name().is_some_and(|n| n == DEVCONTAINER_DIR) {
+        file_dir.parent().unwrap_or(file_dir)
+    } else {
+        file_dir
+    };
+    let local_folder = workspace.to_string_lossy().into_owned();
+    let substitute = |value: &str| substitute(value, &local_folder);
+
+    let workspace_folder = devcontainer
+        .workspace_folder
+        .as_deref()
+        .map(substitute)
+        .or_else(|| {
+            devcontainer
+                .workspace_mount
+                .as_deref()
+                .and_then(mount_target)
+                .map(substitute)
+        })
+        .or_else(|| {
+            // Without compose, the workspace is mounted in `/workspaces` by default
+            devcontainer.service.is_none().then(|| {
+                let name = workspace.file_name().unwrap_or_default().to_string_lossy();
+                format!("/workspaces/{name}")
+            })
+        });
+
+    let compose_file = devcontainer
+        .docker_compose_file
+        .and_then(|files| match files {
+            ComposeFiles::One(file) => Some(file),
+            ComposeFiles::Many(files) => files.into_iter().next(),
+        });
+
+    Ok(ProxyConfigToml {
+        local_path: Some(escape(&local_folder)),
+        docker_internal_path: workspace_folder.as_deref().map(escape),
+        compose_service: devcontainer
+            .service
+            .as_deref()
+            .map(substitute)
+            .map(|s| escape(&s)),
+        compose_file: compose_file
+            .map(|f| escape(&file_dir.join(substitute(&f)).to_string_lossy())),
+        user: devcontainer
+            .remote_user
+            .or(devcontainer.container_user)
+            .as_deref()
+            .map(substitute)
+            .map(|u| escape(&u)),
+        devcontainer_folder: devcontainer
+            .service
+            .is_none()
+            .then(|| escape(&local_folder)),
+        ..Default::default()
+    })
+}
+
+/// Replace the dev container variables that are known in the host
+fn substitute(value: &str, local_folder: &str) -> String {
+    let basename = Path::new(local_folder)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    let mut result = value
+        .replace("${localWorkspaceFolderBasename}", &basename)
+        .replace("${localWorkspaceFolder}", local_folder);
+
+    while let Some(start) = result.find("${localEnv:") {
+        let Some(len) = result[start..].find('}') else {
+            break;
+        };
+        let var = &result[start + "${localEnv:".len()..start + len];
+        let (name, default) = var.split_once(':').unwrap_or((var, ""));
+        let value = std::env::var(name).unwrap_or_else(|_| default.to_string());
+        result.replace_range(start..=start + len, &value);
+    }
+
+    result
+}
+
+/// The values of `devcontainer.json` do not use the lspdock variables
+fn escape(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+/// The `target` of a mount written as `source=...,target=...,type=bind`
+fn mount_target(mount: &str) -> Option<&str> {
+    mount.split(',').find_map(|part| {
+        let (key, value) = part.split_once('=')?;
+        matches!(key.trim(), "target" | "dst" | "destination").then_some(value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_devcontainer_with_comments() {
+        let path = Path::new("/home/me/dev/shop/.devcontainer/devcontainer.json");
+        let config = parse_devcontainer(
+            r#"{
+                // The image is not used by lspdock
+                "image": "mcr.microsoft.com/devcontainers/python:3",
+                "workspaceFolder": "/workspaces/${localWorkspaceFolderBasename}",
+                /* the user of `docker exec` */
+                "remoteUser": "vscode",
+            }"#,
+            path,
+        )
+        .unwrap();
+
+        assert_eq!(config.local_path.as_deref(), Some("/home/me/dev/shop"));
+        assert_eq!(
+            config.docker_internal_path.as_deref(),
+            Some("/workspaces/shop")
+        );
+        assert_eq!(config.user.as_deref(), Some("vscode"));
+        assert_eq!(
+            config.devcontainer_folder.as_deref(),
+            Some("/home/me/dev/shop")
+        );
+        assert_eq!(config.compose_service, None);
+    }
+
+    #[test]
+    fn parse_devcontainer_with_compose() {
+        let path = Path::new("/home/me/dev/shop/.devcontainer/devcontainer.json");
+        let config = parse_devcontainer(
+            r#"{
+                "dockerComposeFile": ["../docker-compose.yml", "compose.extend.yml"],
+                "service": "web",
+                "workspaceMount": "source=${localWorkspaceFolder},target=/usr/src/app,type=bind",
+            }"#,
+            path,
+        )
+        .unwrap();
+
+        assert_eq!(config.compose_service.as_deref(), Some("web"));
+        assert_eq!(
+            config.compose_file.as_deref(),
+            Some("/home/me/dev/shop/.devcontainer/../docker-compose.yml")
+        );
+        assert_eq!(config.docker_internal_path.as_deref(), Some("/usr/src/app"));
+        assert_eq!(config.devcontainer_folder, None);
+    }
+}
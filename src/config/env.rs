@@ -0,0 +1,161 @@
+use super::validate::{fields_of, suggest};
+use super::{CONFIG_ENV, ProxyConfigToml};
+
+const ENV_PREFIX: &str = "LSPDOCK_";
+
+/// Names of the CLI flags that differ from the field names
+const ALIASES: &[(&str, &str)] = &[
+    ("DOCKER_PATH", "docker_internal_path"),
+    ("EXEC", "executable"),
+    ("PIDS", "patch_pid"),
+];
+
+/// A configuration layer and the variable that sets it
+pub(super) type EnvLayer = (String, ProxyConfigToml);
+
+/// Build a configuration layer for each `LSPDOCK_<FIELD>` variable, e.g. `LSPDOCK_CONTAINER`.
+/// The value is a string; when the field is not a string, it is read as a TOML value or as a
+/// comma-separated list, e.g. `LSPDOCK_PIDS=pyright-langserver,pylsp`. The CLI names of the
+/// flags are accepted too, and the field names have precedence over them. The unknown
+/// variables are skipped, the second list has a warning for each of them.
+pub(super) fn env_layers(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<(Vec<EnvLayer>, Vec<String>), String> {
+    let fields = fields_of::<ProxyConfigToml>();
+    let mut layers = vec![];
+    let mut warnings = vec![];
+
+    for (var, value) in vars {
+        // The config file is not a field
+        if var == CONFIG_ENV {
+            continue;
+        }
+        let Some(name) = var.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+
+        let lowercase = name.to_ascii_lowercase();
+        let (field, is_alias) = match ALIASES.iter().find(|(alias, _)| *alias == name) {
+            Some((_, field)) => (*field, true),
+            // `extends` is resolved against the file that declares it
+            None => match fields.iter().find(|f| **f == lowercase && **f != "extends") {
+                Some(field) => (*field, false),
+                None => {
+                    let mut warning = format!("unknown variable {var} is ignored");
+                    if let Some(field) = suggest(&lowercase, fields) {
+                        let suggestion = format!("{ENV_PREFIX}{}", field.to_ascii_uppercase());
+                        warning.push_str(&format!(", did you mean {suggestion}?"));
+                    }
+                    warnings.push(warning);
+                    continue;
+                }
+            },
+        };
+
+        let layer = parse_field(field, &value)
+            .ok_or_else(|| format!("{var} has an invalid value for {field}: {value}"))?;
+        layers.push((!is_alias, var, layer));
+    }
+
+    layers.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    let layers = layers
+        .into_iter()
+        .map(|(_, var, layer)| (var, layer))
+        .collect();
+    Ok((layers, warnings))
+}
+
+/// Read the value as a string, a TOML value or a comma-separated list, the first that matches
+/// the type of the field
+fn parse_field(field: &str, value: &str) -> Option<ProxyConfigToml> {
+    let as_string = toml::Value::String(value.to_string()).to_string();
+    let as_list = toml::Value::Array(
+        value
+            .split(',')
+            .map(|v| toml::Value::String(v.trim().to_string()))
+            .collect(),
+    )
+    .to_string();
+
+    [as_string.as_str(), value, as_list.as_str()]
+        .into_iter()
+        .find_map(|v| toml::from_str(&format!("{field} = {v}")).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MergeList;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env_variables_layers() {
+        let (layers, warnings) = env_layers(vars(&[
+            ("LSPDOCK_CONTAINER", "app-web-1"),
+            ("LSPDOCK_PIDS", "pyright-langserver, pylsp"),
+            ("LSPDOCK_EXECUTABLE", "pylsp"),
+            ("LSPDOCK_EXEC", "ruff"),
+            ("LSPDOCK_PATTERN", "/home/me/dev"),
+            ("LSPDOCK_PROJECT_MARKERS", r#"{ append = ["setup.cfg"] }"#),
+            ("LSPDOCK_CONFIG", "/etc/lspdock.toml"),
+            ("HOME", "/home/me"),
+        ]))
+        .unwrap();
+        assert!(warnings.is_empty());
+
+        let names: Vec<&str> = layers.iter().map(|(var, _)| var.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "LSPDOCK_EXEC",
+                "LSPDOCK_PIDS",
+                "LSPDOCK_CONTAINER",
+                "LSPDOCK_EXECUTABLE",
+                "LSPDOCK_PATTERN",
+                "LSPDOCK_PROJECT_MARKERS"
+            ]
+        );
+
+        assert_eq!(layers[0].1.executable.as_deref(), Some("ruff"));
+        assert_eq!(
+            layers[1].1.patch_pid,
+            Some(MergeList::Replace(vec![
+                "pyright-langserver".into(),
+                "pylsp".into()
+            ]))
+        );
+        assert_eq!(
+            layers[4].1.pattern,
+            Some(MergeList::Replace(vec!["/home/me/dev".into()]))
+        );
+        assert_eq!(
+            layers[5].1.project_markers,
+            Some(MergeList::Append {
+                append: vec!["setup.cfg".into()]
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_env_variable() {
+        let (layers, warnings) = env_layers(vars(&[
+            ("LSPDOCK_CONTAINR", "app"),
+            ("LSPDOCK_FOO", "1"),
+            ("LSPDOCK_USER", "vscode"),
+        ]))
+        .unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(
+            warnings,
+            [
+                "unknown variable LSPDOCK_CONTAINR is ignored, did you mean LSPDOCK_CONTAINER?",
+                "unknown variable LSPDOCK_FOO is ignored"
+            ]
+        );
+    }
+}
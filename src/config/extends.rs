@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+use super::manifest::{is_manifest, parse_manifest};
+use super::provider::{ConfigParseError, MergeList, ProxyConfigToml};
+use super::validate::unknown_keys;
+
+/// Read a TOML config file, or the config embedded in a manifest, and the files that it extends,
+/// in the order they are merged: the extended files first, in the order of `extends`, and the
+/// file itself last. The relative paths of `extends` are resolved against the directory of the
+/// file that declares them.
+pub(super) fn load_with_extends(
+    path: &Path,
+) -> Result<Vec<(PathBuf, ProxyConfigToml)>, ConfigParseError> {
+    let mut layers = vec![];
+    load(path, &mut vec![], &mut layers)?;
+    Ok(layers)
+}
+
+fn load(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    layers: &mut Vec<(PathBuf, ProxyConfigToml)>,
+) -> Result<(), ConfigParseError> {
+    // The canonical path detects the cycles written with different relative paths
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Some(start) = chain.iter().position(|p| *p == canonical) {
+        let cycle: Vec<String> = chain[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(ConfigParseError::InvalidExtends(format!(
+            "cycle detected: {}",
+            cycle.join(" → ")
+        )));
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        // The error of the first file is reported as any other config file
+        Err(e) if chain.is_empty() => return Err(e.into()),
+        Err(e) => {
+            let parent = chain.last().map(|p| p.display().to_string());
+            return Err(ConfigParseError::InvalidExtends(format!(
+                "{} extends {}, that cannot be read: {e}",
+                parent.unwrap_or_default(),
+                path.display()
+            )));
+        }
+    };
+
+    let mut layer = if is_manifest(path) {
+        parse_manifest(&content, path)?
+    } else {
+        let layer: ProxyConfigToml = toml::from_str(&content)?;
+        let unknown = unknown_keys(&content);
+        if !unknown.is_empty() {
+            return Err(ConfigParseError::UnknownKeys(path.to_path_buf(), unknown));
+        }
+        layer
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    chain.push(canonical);
+    for extended in layer
+        .extends
+        .take()
+        .map(MergeList::into_vec)
+        .unwrap_or_default()
+    {
+        load(&dir.join(extended), chain, layers)?;
+    }
+    chain.pop();
+
+    layers.push((path.to_path_buf(), layer));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn load_extended_files_first() {
+        let root = temp_dir("load_with_extends");
+        let service = root.join("services").join("api");
+        let shared = root.join("shared");
+        std::fs::create_dir_all(&service).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+
+        std::fs::write(shared.join("base.toml"), r#"log_level = "debug""#).unwrap();
+        std::fs::write(
+            shared.join("python.toml"),
+            "extends = \"base.toml\"\nexecutable = \"pylsp\"",
+        )
+        .unwrap();
+        std::fs::write(
+            service.join("lspdock.toml"),
+            "extends = [\"../../shared/python.toml\"]\ncontainer = \"api\"",
+        )
+        .unwrap();
+
+        let layers = load_with_extends(&service.join("lspdock.toml")).unwrap();
+        let files: Vec<&str> = layers
+            .iter()
+            .map(|(p, _)| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(files, ["base.toml", "python.toml", "lspdock.toml"]);
+        assert_eq!(layers[0].1.log_level.as_deref(), Some("debug"));
+        assert_eq!(layers[2].1.container.as_deref(), Some("api"));
+
+        // A file that extends the file that extends it
+        std::fs::write(
+            shared.join("base.toml"),
+            "extends = \"../services/api/lspdock.toml\"",
+        )
+        .unwrap();
+        let error = load_with_extends(&service.join("lspdock.toml")).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("cycle detected"), "{message}");
+        assert!(message.ends_with("lspdock.toml"), "{message}");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
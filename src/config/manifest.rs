@@ -0,0 +1,220 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::provider::{ConfigParseError, ProxyConfigToml};
+use super::validate::{UnknownKey, fields_of, line_column, suggest, unknown_keys_in};
+
+const PYPROJECT: &str = "pyproject.toml";
+const CARGO: &str = "Cargo.toml";
+const PACKAGE_JSON: &str = "package.json";
+const PACKAGE_JSON_KEY: &str = "lspdock";
+
+/// Project manifests that can embed the config, searched after `lspdock.toml` in each directory
+pub(super) const MANIFESTS: &[&str] = &[PYPROJECT, CARGO, PACKAGE_JSON];
+
+/// Tables of the TOML manifests that hold the config, the first one present is used
+const PYPROJECT_SECTIONS: &[&[&str]] = &[&["tool", "lspdock"]];
+const CARGO_SECTIONS: &[&[&str]] = &[
+    &["package", "metadata", "lspdock"],
+    &["workspace", "metadata", "lspdock"],
+];
+
+/// Whether the file is a manifest, by its name
+pub(super) fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| MANIFESTS.contains(&n))
+}
+
+/// Whether the manifest embeds a config; a manifest that cannot be parsed has no config
+pub(super) fn has_config(path: &Path) -> bool {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return false;
+    };
+
+    match file_name(path) {
+        PACKAGE_JSON => serde_json::from_str::<serde_json::Value>(&content)
+            .is_ok_and(|json| json.get(PACKAGE_JSON_KEY).is_some()),
+        name => content
+            .parse::<toml::Table>()
+            .is_ok_and(|table| find_section(&table, toml_sections(name)).is_some()),
+    }
+}
+
+/// Build a configuration layer from the config embedded in a manifest: `[tool.lspdock]` of
+/// `pyproject.toml`, `[package.metadata.lspdock]` or `[workspace.metadata.lspdock]` of
+/// `Cargo.toml`, and the `"lspdock"` key of `package.json`. A manifest without config is an
+/// empty layer.
+pub(super) fn parse_manifest(
+    content: &str,
+    path: &Path,
+) -> Result<ProxyConfigToml, ConfigParseError> {
+    match file_name(path) {
+        PACKAGE_JSON => parse_package_json(content, path),
+        name => {
+            let table: toml::Table = content.parse()?;
+            let Some((section, value)) = find_section(&table, toml_sections(name)) else {
+                return Ok(ProxyConfigToml::default());
+            };
+
+            let layer = ProxyConfigToml::deserialize(value.clone())?;
+            let unknown = unknown_keys_in(content, section);
+            if !unknown.is_empty() {
+                return Err(ConfigParseError::UnknownKeys(path.to_path_buf(), unknown));
+            }
+            Ok(layer)
+        }
+    }
+}
+
+fn parse_package_json(content: &str, path: &Path) -> Result<ProxyConfigToml, ConfigParseError> {
+    let invalid = |e: serde_json::Error| {
+        ConfigParseError::InvalidValues(vec![format!("{}: {e}", path.display())])
+    };
+
+    let mut json: serde_json::Value = serde_json::from_str(content).map_err(invalid)?;
+    let Some(value) = json.get_mut(PACKAGE_JSON_KEY).map(serde_json::Value::take) else {
+        return Ok(ProxyConfigToml::default());
+    };
+
+    // JSON has no spans, the keys are located by their first occurrence after "lspdock"
+    let fields = fields_of::<ProxyConfigToml>();
+    let start = content
+        .find(&format!("\"{PACKAGE_JSON_KEY}\""))
+        .unwrap_or_default();
+    let unknown: Vec<UnknownKey> = value
+        .as_object()
+        .into_iter()
+        .flat_map(|object| object.keys())
+        .filter(|key| !fields.contains(&key.as_str()))
+        .map(|key| {
+            let offset = content[start..]
+                .find(&format!("\"{key}\""))
+                .map_or(start, |o| start + o);
+            let (line, column) = line_column(content, offset);
+            UnknownKey {
+                key: format!("{PACKAGE_JSON_KEY}.{key}"),
+                line,
+                column,
+                suggestion: suggest(key, fields),
+            }
+        })
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ConfigParseError::UnknownKeys(path.to_path_buf(), unknown));
+    }
+
+    serde_json::from_value(value).map_err(invalid)
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
+
+fn toml_sections(name: &str) -> &'static [&'static [&'static str]] {
+    match name {
+        PYPROJECT => PYPROJECT_SECTIONS,
+        CARGO => CARGO_SECTIONS,
+        _ => &[],
+    }
+}
+
+/// The first section present in the table, with its value
+fn find_section<'a>(
+    table: &'a toml::Table,
+    sections: &'static [&'static [&'static str]],
+) -> Option<(&'static [&'static str], &'a toml::Value)> {
+    sections.iter().find_map(|section| {
+        let (first, rest) = section.split_first()?;
+        let value = rest
+            .iter()
+            .try_fold(table.get(*first)?, |value, name| value.get(*name))?;
+        value.is_table().then_some((*section, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::provider::MergeList;
+
+    #[test]
+    fn parse_toml_manifests() {
+        let config = parse_manifest(
+            r#"
+[project]
+name = "shop"
+
+[tool.lspdock]
+container = "shop-web-1"
+patch_pid = ["pyright-langserver"]
+
+[[tool.lspdock.mappings]]
+local_path = "/home/me/dev/libs"
+docker_internal_path = "/opt/libs"
+"#,
+            Path::new("/home/me/dev/shop/pyproject.toml"),
+        )
+        .unwrap();
+        assert_eq!(config.container.as_deref(), Some("shop-web-1"));
+        assert_eq!(config.mappings.map(MergeList::into_vec).unwrap().len(), 1);
+
+        let config = parse_manifest(
+            r#"
+[workspace]
+members = ["api"]
+
+[workspace.metadata.lspdock]
+executable = "rust-analyzer"
+"#,
+            Path::new("/home/me/dev/shop/Cargo.toml"),
+        )
+        .unwrap();
+        assert_eq!(config.executable.as_deref(), Some("rust-analyzer"));
+
+        let error = parse_manifest(
+            "[package]\nname = \"api\"\n\n[package.metadata.lspdock]\ncontaner = \"api\"\n",
+            Path::new("/home/me/dev/shop/api/Cargo.toml"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown keys in config file:\n/home/me/dev/shop/api/Cargo.toml:5:1: unknown key \
+             `package.metadata.lspdock.contaner`, did you mean `container`?"
+        );
+    }
+
+    #[test]
+    fn parse_package_json_key() {
+        let path = Path::new("/home/me/dev/web/package.json");
+        let config = parse_manifest(
+            r#"{
+  "name": "web",
+  "lspdock": {
+    "compose_service": "web",
+    "pattern": "/home/me/dev"
+  }
+}"#,
+            path,
+        )
+        .unwrap();
+        assert_eq!(config.compose_service.as_deref(), Some("web"));
+        assert_eq!(
+            config.pattern,
+            Some(MergeList::Replace(vec!["/home/me/dev".into()]))
+        );
+
+        let error =
+            parse_manifest(r#"{"name": "web", "lspdock": {"containr": "web"}}"#, path).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigParseError::UnknownKeys(_, keys) if keys[0].column == 29
+        ));
+
+        let config = parse_manifest(r#"{"name": "web"}"#, path).unwrap();
+        assert_eq!(config.container, None);
+    }
+}
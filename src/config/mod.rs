@@ -1,19 +1,44 @@
 mod cli;
+mod devcontainer;
+mod env;
+mod extends;
+mod manifest;
+mod pattern;
+mod provenance;
 mod provider;
+mod runtime;
+mod validate;
 mod variables;
 
-use std::{env::current_dir, path::PathBuf};
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+};
 
-pub use cli::Cli;
+pub use cli::{Cli, Commands, ConfigCommand};
+use devcontainer::find_devcontainer;
+use manifest::{MANIFESTS, has_config};
+pub use provenance::{Source, render as render_config, shell_join};
+use provider::MergeList;
 #[allow(unused)] // In unix encode_path is not used
-pub use provider::{ProxyConfig, ProxyConfigToml, encode_path};
+pub use provider::{ComposeService, PathMapping, ProxyConfig, ProxyConfigToml, encode_path};
+pub use runtime::{Kubernetes, Runtime};
 
 const CONFIG_NAME: &str = "lspdock.toml";
+const HIDDEN_CONFIG_NAME: &str = ".lspdock.toml";
+const CONFIG_ENV: &str = "LSPDOCK_CONFIG";
 
 #[derive(Debug)]
 pub enum PathType {
+    /// Project file, found from the current working directory
     Cwd,
     Home,
+    #[cfg_attr(windows, allow(dead_code))] // There is no system config in Windows
+    System,
+    /// File passed with `--config` or `LSPDOCK_CONFIG`
+    Explicit,
+    /// `devcontainer.json` of the project, used when there is no project file
+    DevContainer,
 }
 
 #[derive(Debug)]
@@ -22,29 +47,155 @@ pub struct ConfigPath {
     r#type: PathType,
 }
 
-/// Get the configuration using the hierarchy order:
+/// Get every configuration file that exists, from the lowest to the highest precedence:
 ///
-/// 1. Project path
-/// 2. .config directory in the home
-pub fn resolve_config_path() -> Option<ConfigPath> {
-    let cwd = current_dir().ok()?;
-    let cwd_config = cwd.join(CONFIG_NAME);
-    if cwd_config.exists() {
-        return Some(ConfigPath {
-            path: cwd_config,
-            r#type: PathType::Cwd,
-        });
+/// 1. System configuration (`/etc/lspdock` in Unix)
+/// 2. lspdock directory in `$XDG_CONFIG_HOME`, or in the .config directory in the home
+/// 3. File passed with `--config` or `LSPDOCK_CONFIG`; otherwise the nearest project file,
+///    searched upward from the current directory to the git root, or the `devcontainer.json`
+///    of the project if there is no project file. A `pyproject.toml`, `Cargo.toml` or
+///    `package.json` that embeds the config is a project file too.
+pub fn resolve_config_path(cli: &Cli) -> Vec<ConfigPath> {
+    let mut paths = vec![];
+
+    #[cfg(unix)]
+    {
+        let system_config = PathBuf::from("/etc/lspdock").join(CONFIG_NAME);
+        if system_config.exists() {
+            paths.push(ConfigPath {
+                path: system_config,
+                r#type: PathType::System,
+            });
+        }
     }
 
-    let home = dirs::home_dir().unwrap_or_default();
-    let home_config = home.join(".config").join("lspdock").join(CONFIG_NAME);
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"));
+    let home_config = config_dir.join("lspdock").join(CONFIG_NAME);
 
     if home_config.exists() {
-        return Some(ConfigPath {
+        paths.push(ConfigPath {
             path: home_config,
             r#type: PathType::Home,
         });
     }
 
+    let explicit = cli
+        .config
+        .clone()
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+
+    if let Some(path) = explicit {
+        // The file is required when it is explicitly provided, let the reader report it
+        paths.push(ConfigPath {
+            path,
+            r#type: PathType::Explicit,
+        });
+    } else if let Ok(cwd) = current_dir() {
+        if let Some(path) = find_project_config(&cwd) {
+            paths.push(ConfigPath {
+                path,
+                r#type: PathType::Cwd,
+            });
+        } else if let Some(path) = find_devcontainer(&cwd) {
+            paths.push(ConfigPath {
+                path,
+                r#type: PathType::DevContainer,
+            });
+        }
+    }
+
+    paths
+}
+
+/// JSON Schema of `lspdock.toml`, generated from [`ProxyConfigToml`]
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(ProxyConfigToml);
+    serde_json::to_string_pretty(&schema).expect("the schema is valid JSON")
+}
+
+/// Search `lspdock.toml` or `.lspdock.toml`, or a manifest that embeds the config, from `start`
+/// upward, stopping at the git root or at the filesystem root. In a directory, `lspdock.toml`
+/// has precedence over the manifests.
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        for name in [CONFIG_NAME, HIDDEN_CONFIG_NAME] {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+
+        for name in MANIFESTS {
+            let candidate = dir.join(name);
+            if candidate.is_file() && has_config(&candidate) {
+                return Some(candidate);
+            }
+        }
+
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn find_project_config_upward() {
+        let root = temp_dir("find_project_config");
+        let nested = root.join("repo").join("src").join("module");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(root.join("repo").join(".git")).unwrap();
+
+        // Above the git root, it must be ignored
+        std::fs::write(root.join(CONFIG_NAME), "").unwrap();
+        assert_eq!(find_project_config(&nested), None);
+
+        let hidden = root.join("repo").join(HIDDEN_CONFIG_NAME);
+        std::fs::write(&hidden, "").unwrap();
+        assert_eq!(find_project_config(&nested), Some(hidden.clone()));
+
+        // A manifest without config is not a config file
+        let manifest = root
+            .join("repo")
+            .join("src")
+            .join("module")
+            .join("pyproject.toml");
+        std::fs::write(&manifest, "[project]\nname = \"module\"\n").unwrap();
+        assert_eq!(find_project_config(&nested), Some(hidden));
+
+        let nearest = root.join("repo").join("src").join(CONFIG_NAME);
+        std::fs::write(&nearest, "").unwrap();
+        assert_eq!(find_project_config(&nested), Some(nearest));
+
+        std::fs::write(&manifest, "[tool.lspdock]\ncontainer = \"module\"\n").unwrap();
+        assert_eq!(find_project_config(&nested), Some(manifest));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn json_schema_matches_the_fields() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+
+        let mut fields: Vec<&str> = validate::fields_of::<ProxyConfigToml>().to_vec();
+        let mut keys: Vec<&str> = properties.keys().map(String::as_str).collect();
+        fields.sort();
+        keys.sort();
+        assert_eq!(fields, keys);
+
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(properties["log_level"]["default"], "info");
+        assert!(properties["pattern"].to_string().contains("anyOf"));
+    }
+}
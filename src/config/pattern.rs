@@ -0,0 +1,228 @@
+use std::{fmt::Display, path::Path};
+
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
+
+use super::MergeList;
+
+const REGEX_PREFIX: &str = "regex:";
+
+/// A single `pattern` or `exclude` entry. Globs and plain paths match the directory and its
+/// children, `regex:` entries must match the full path of the directory or one of its parents.
+#[derive(Debug)]
+enum Rule {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Rule {
+    fn new(source: &str) -> Result<Self, String> {
+        if let Some(re) = source.strip_prefix(REGEX_PREFIX) {
+            let re = RegexBuilder::new(&format!("^(?:{re})$"))
+                .case_insensitive(cfg!(windows))
+                .build()
+                .map_err(|e| format!("invalid regex {source}: {e}"))?;
+            return Ok(Self::Regex(re));
+        }
+
+        let glob = norm_for_match(source);
+        let glob = match glob.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+
+        let matcher = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .map(|g: Glob| g.compile_matcher())
+            .map_err(|e| format!("invalid glob {source}: {e}"))?;
+
+        Ok(Self::Glob(matcher))
+    }
+
+    fn is_match(&self, dir: &Path) -> bool {
+        let dir = dir.to_string_lossy();
+        match self {
+            Self::Glob(g) => g.is_match(dir.as_ref()),
+            Self::Regex(r) => r.is_match(&dir),
+        }
+    }
+}
+
+/// The rule that decided whether Docker is used
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// There is no pattern, every directory matches
+    NoPattern,
+    Included(String),
+    Excluded(String),
+    NotIncluded,
+}
+
+impl Decision {
+    pub fn matches(&self) -> bool {
+        matches!(self, Self::NoPattern | Self::Included(_))
+    }
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPattern => write!(f, "no pattern is configured"),
+            Self::Included(rule) => write!(f, "pattern `{rule}` matches"),
+            Self::Excluded(rule) => write!(f, "exclude `{rule}` matches"),
+            Self::NotIncluded => write!(f, "no pattern matches"),
+        }
+    }
+}
+
+/// Check the `cwd` against the `exclude` and `pattern` rules, the exclusions are checked first
+pub fn match_cwd(cwd: &Path, pattern: &[String], exclude: &[String]) -> Result<Decision, String> {
+    let cwd = norm_for_match(cwd.to_string_lossy());
+    let cwd = Path::new(&cwd);
+
+    let first_match = |sources: &[String]| -> Result<Option<String>, String> {
+        for source in sources.iter().filter(|s| !s.is_empty()) {
+            let rule = Rule::new(source)?;
+            if cwd.ancestors().any(|dir| rule.is_match(dir)) {
+                return Ok(Some(source.clone()));
+            }
+        }
+        Ok(None)
+    };
+
+    if let Some(rule) = first_match(exclude)? {
+        return Ok(Decision::Excluded(rule));
+    }
+
+    if pattern.iter().all(|p| p.is_empty()) {
+        return Ok(Decision::NoPattern);
+    }
+
+    Ok(match first_match(pattern)? {
+        Some(rule) => Decision::Included(rule),
+        None => Decision::NotIncluded,
+    })
+}
+
+pub(super) fn norm_for_match<S: AsRef<str>>(s: S) -> String {
+    #[allow(unused_mut)]
+    let mut t = s.as_ref().replace('\\', "/");
+
+    #[cfg(windows)]
+    {
+        t.make_ascii_lowercase();
+    }
+
+    t
+}
+
+/// A single string, a list of strings or a `{ append = [...] }` list
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub(super) enum OneOrMany {
+    One(String),
+    Many(MergeList),
+}
+
+/// Deserialize a single string, a list of strings or a `{ append = [...] }` list
+pub(super) fn string_or_list<'de, D>(deserializer: D) -> Result<Option<MergeList>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|v| match v {
+            OneOrMany::One(s) => MergeList::Replace(vec![s]),
+            OneOrMany::Many(v) => v,
+        }),
+    )
+}
+
+/// Warn about an entry that is neither an absolute path, a glob that starts with a wildcard
+/// nor a `regex:` entry. The entries are anchored to the root, so a relative entry like `dev`,
+/// that used to match as a substring, never matches.
+pub(super) fn relative_rule_warning(source: &str) -> Option<String> {
+    let path = norm_for_match(source);
+    let anchored = source.is_empty()
+        || source.starts_with(REGEX_PREFIX)
+        || path.starts_with(['/', '*', '?', '[', '{'])
+        || Path::new(source).is_absolute();
+
+    (!anchored).then(|| {
+        format!("`{source}` is not an absolute path and never matches, use `**/{source}` to match it anywhere")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decide(cwd: &str, pattern: &[&str], exclude: &[&str]) -> Decision {
+        let pattern: Vec<String> = pattern.iter().map(|p| p.to_string()).collect();
+        let exclude: Vec<String> = exclude.iter().map(|p| p.to_string()).collect();
+        match_cwd(Path::new(cwd), &pattern, &exclude).unwrap()
+    }
+
+    #[test]
+    fn anchored_plain_paths() {
+        assert!(decide("/home/me/dev", &["/home/me/dev"], &[]).matches());
+        assert!(decide("/home/me/dev/api/src", &["/home/me/dev/"], &[]).matches());
+        assert!(!decide("/home/me/dev-old/scratch", &["/home/me/dev"], &[]).matches());
+        assert_eq!(decide("/home/me/work", &[], &[]), Decision::NoPattern);
+    }
+
+    #[test]
+    fn globs_regex_and_excludes() {
+        assert_eq!(
+            decide("/home/me/dev/api/src", &["/home/*/dev/a*"], &[]),
+            Decision::Included("/home/*/dev/a*".into())
+        );
+        assert!(!decide("/home/me/dev", &["/home/*/dev/a*"], &[]).matches());
+        assert!(decide("/srv/a/b/c/project", &["/srv/**/project"], &[]).matches());
+        assert!(
+            decide(
+                "/home/me/dev/api",
+                &["regex:/home/[a-z]+/dev/(api|web)"],
+                &[]
+            )
+            .matches()
+        );
+        assert!(!decide("/home/me/dev/apis", &["regex:/home/[a-z]+/dev/api"], &[]).matches());
+
+        assert_eq!(
+            decide(
+                "/home/me/dev/sandbox/x",
+                &["/home/me/dev"],
+                &["/home/me/dev/sandbox"]
+            ),
+            Decision::Excluded("/home/me/dev/sandbox".into())
+        );
+        assert_eq!(
+            decide(
+                "/home/me/work",
+                &["/home/me/dev"],
+                &["/home/me/dev/sandbox"]
+            ),
+            Decision::NotIncluded
+        );
+    }
+
+    #[test]
+    fn relative_rules() {
+        assert!(relative_rule_warning("dev").is_some());
+        assert!(relative_rule_warning("~/dev").is_some());
+        assert!(relative_rule_warning("/home/me/dev").is_none());
+        assert!(relative_rule_warning("**/dev").is_none());
+        assert!(relative_rule_warning("regex:.*/dev").is_none());
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let pattern = vec!["regex:(".to_string()];
+        assert!(match_cwd(Path::new("/home"), &pattern, &[]).is_err());
+        let pattern = vec!["/home/[".to_string()];
+        assert!(match_cwd(Path::new("/home"), &pattern, &[]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::BTreeMap, fmt::Display, fmt::Write, path::PathBuf};
+
+use crate::config::{
+    ConfigPath, ProxyConfig,
+    provider::{display_env, display_mappings},
+};
+
+/// Where the value of a field comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    /// A `[[profile]]` of the file
+    Profile(PathBuf),
+    /// A `LSPDOCK_*` environment variable
+    Env(String),
+    Cli,
+    /// Computed by lspdock, e.g. from the binary name or the container mounts
+    Derived(String),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Profile(path) => write!(f, "profile in {}", path.display()),
+            Self::Env(var) => write!(f, "environment variable {var}"),
+            Self::Cli => write!(f, "CLI flag"),
+            Self::Derived(from) => write!(f, "derived from {from}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: Source,
+    /// The value before the variable expansion, if it contained variables
+    pub raw: Option<String>,
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+        if let Some(raw) = &self.raw {
+            write!(f, ", expanded from {raw:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Provenance of every top-level field, keyed by the field name
+#[derive(Debug, Clone, Default)]
+pub struct Sources(BTreeMap<&'static str, Provenance>);
+
+/// Values of the top-level fields, rendered as strings
+pub(super) type FieldValues = Vec<(&'static str, Option<String>)>;
+
+impl Sources {
+    /// Record the fields provided by a configuration layer. `raw` are the values before the
+    /// variable expansion, `expanded` after it, and `applied` after applying the profiles.
+    pub(super) fn record_layer(
+        &mut self,
+        raw: &FieldValues,
+        expanded: &FieldValues,
+        applied: &FieldValues,
+        source: Source,
+        profile_source: Source,
+    ) {
+        for (((name, raw), (_, expanded)), (_, applied)) in raw.iter().zip(expanded).zip(applied) {
+            let Some(value) = applied else {
+                continue;
+            };
+
+            let (source, raw) = if applied != expanded {
+                (profile_source.clone(), None)
+            } else {
+                (source.clone(), raw.clone().filter(|r| r != value))
+            };
+
+            self.set(name, source, raw);
+        }
+    }
+
+    pub fn set(&mut self, name: &'static str, source: Source, raw: Option<String>) {
+        self.0.insert(name, Provenance { source, raw });
+    }
+
+    pub(super) fn extend(&mut self, other: Sources) {
+        self.0.extend(other.0);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provenance> {
+        self.0.get(name)
+    }
+}
+
+/// Render the resolved config for `lspdock config show`: every field with its source,
+/// the Docker decision and the command line that would be spawned
+pub fn render(
+    config_paths: &[ConfigPath],
+    config: &ProxyConfig,
+    cmd: &str,
+    args: &[String],
+) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Configuration files:");
+    if config_paths.is_empty() {
+        let _ = writeln!(out, "  (none)");
+    }
+    for cp in config_paths {
+        let _ = writeln!(out, "  {} ({:?})", cp.path.display(), cp.r#type);
+    }
+
+    let compose = config.compose.as_ref();
+    let fields = [
+        ("container", config.container.clone()),
+        (
+            "compose_service",
+            compose.map(|c| c.service.clone()).unwrap_or_default(),
+        ),
+        (
+            "compose_project",
+            compose.and_then(|c| c.project.clone()).unwrap_or_default(),
+        ),
+        (
+            "compose_file",
+            compose.and_then(|c| c.file.clone()).unwrap_or_default(),
+        ),
+        ("user", config.user.clone().unwrap_or_default()),
+        ("runtime", config.runtime.to_string()),
+        (
+            "launcher",
+            config
+                .launcher
+                .as_deref()
+                .map(shell_join)
+                .unwrap_or_default(),
+        ),
+        ("kubernetes", config.kubernetes.to_string()),
+        ("docker_internal_path", config.docker_internal_path.clone()),
+        ("local_path", config.local_path.clone()),
+        ("executable", config.executable.clone()),
+        (
+            "patch_pid",
+            config.patch_pid.clone().unwrap_or_default().join(", "),
+        ),
+        ("log_level", config.log_level.clone()),
+        ("pattern", config.pattern.join(", ")),
+        ("exclude", config.exclude.join(", ")),
+        ("project_markers", config.project_markers.join(", ")),
+        ("variables", display_env(&config.variables)),
+        ("mappings", display_mappings(&config.mappings)),
+        ("command", config.command.clone()),
+        ("args", shell_join(&config.args)),
+        ("env", display_env(&config.env)),
+    ];
+
+    let _ = writeln!(out, "\nFields:");
+    for (name, value) in fields {
+        let value = if value.is_empty() { "-".into() } else { value };
+        let source = config
+            .sources
+            .get(name)
+            .map(Provenance::to_string)
+            .unwrap_or_else(|| Source::Default.to_string());
+        let _ = writeln!(out, "  {name:<22}{value}  [{source}]");
+    }
+
+    if !config.warnings.is_empty() {
+        let _ = writeln!(out, "\nWarnings:");
+        for warning in &config.warnings {
+            let _ = writeln!(out, "  {warning}");
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "\nDocker: {} ({})",
+        if config.use_docker { "yes" } else { "no" },
+        config.docker_reason
+    );
+
+    // Without an executable there is no command to spawn
+    let command = if config.executable.is_empty() {
+        "-".into()
+    } else {
+        shell_join(&[&[cmd.to_string()], args].concat())
+    };
+    let _ = writeln!(out, "\nCommand:\n  {command}");
+
+    out
+}
+
+/// Join the arguments quoting the ones that the shell would split or expand
+pub fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let safe = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+            if safe {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_layer_sources() {
+        let path = PathBuf::from("/home/me/.config/lspdock/lspdock.toml");
+        let raw = vec![
+            ("container", Some("$PARENT-web-1".to_string())),
+            ("docker_internal_path", Some("/app".to_string())),
+            ("executable", None),
+            ("local_path", None),
+        ];
+        let expanded = vec![
+            ("container", Some("shop-web-1".to_string())),
+            ("docker_internal_path", Some("/app".to_string())),
+            ("executable", None),
+            ("local_path", None),
+        ];
+        let mut applied = expanded.clone();
+        applied[3].1 = Some("/home/me/dev/shop".into());
+
+        let mut sources = Sources::default();
+        sources.record_layer(
+            &raw,
+            &expanded,
+            &applied,
+            Source::File(path.clone()),
+            Source::Profile(path.clone()),
+        );
+
+        let container = sources.get("container").unwrap();
+        assert_eq!(container.source, Source::File(path.clone()));
+        assert_eq!(container.raw.as_deref(), Some("$PARENT-web-1"));
+
+        assert_eq!(sources.get("docker_internal_path").unwrap().raw, None);
+        assert_eq!(
+            sources.get("local_path").unwrap().source,
+            Source::Profile(path)
+        );
+        assert!(sources.get("executable").is_none());
+    }
+
+    #[test]
+    fn render_without_executable() {
+        let config = ProxyConfig {
+            local_path: "/home/me/dev/shop".into(),
+            ..Default::default()
+        };
+
+        let out = render(&[], &config, "", &[]);
+        assert!(out.contains("  executable            -  [default]"));
+        assert!(out.contains("  command               -  [default]"));
+        assert!(out.ends_with("Command:\n  -\n"));
+    }
+
+    #[test]
+    fn quote_shell_arguments() {
+        let args = ["exec", "-e", "A=b c", "it's", ""].map(String::from);
+        assert_eq!(shell_join(&args), r"exec -e 'A=b c' 'it'\''s' ''");
+    }
+}
@@ -1,19 +1,40 @@
 use memchr::memmem::find;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{env::current_dir, error::Error, fmt::Display};
 use tokio_util::bytes::Bytes;
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-use crate::config::variables::{VariableCwd, VariableHome, VariableParent, VariableResolver};
+use crate::config::devcontainer::parse_devcontainer;
+use crate::config::env::env_layers;
+use crate::config::extends::load_with_extends;
+use crate::config::pattern::{
+    Decision, OneOrMany, match_cwd, relative_rule_warning, string_or_list,
+};
+use crate::config::provenance::{FieldValues, Source, Sources};
+use crate::config::runtime::{Kubernetes, Runtime};
+use crate::config::validate::{UnknownKey, invalid_values};
+use crate::config::variables::{DEFAULT_PROJECT_MARKERS, Interpolator, VariableError};
 use crate::config::{Cli, ConfigPath, PathType};
 
 #[derive(Debug)]
 pub enum ConfigParseError {
     FileError(std::io::Error),
     DeserializationError(toml::de::Error),
+    DevContainerError(json5::Error),
     MissingField(&'static str),
+    VariableError(VariableError),
+    InvalidPattern(String),
+    /// Keys of a file that are not fields of the config
+    UnknownKeys(PathBuf, Vec<UnknownKey>),
+    InvalidValues(Vec<String>),
+    /// A `LSPDOCK_*` environment variable that is not valid
+    EnvError(String),
+    /// An `extends` file that cannot be read, or a cycle of `extends`
+    InvalidExtends(String),
 }
 
 impl Error for ConfigParseError {}
@@ -23,7 +44,20 @@ impl Display for ConfigParseError {
         let text = match self {
             Self::FileError(e) => format!("File cannot be readed: {e}"),
             Self::DeserializationError(e) => format!("Error parsing config file: {e}"),
+            Self::DevContainerError(e) => format!("Error parsing devcontainer.json: {e}"),
             Self::MissingField(e) => format!("{e} must be provided"),
+            Self::VariableError(e) => format!("Error expanding variables: {e}"),
+            Self::InvalidPattern(e) => format!("Error parsing pattern: {e}"),
+            Self::UnknownKeys(path, keys) => {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|k| format!("{}:{k}", path.display()))
+                    .collect();
+                format!("Unknown keys in config file:\n{}", keys.join("\n"))
+            }
+            Self::InvalidValues(e) => format!("Invalid config values:\n{}", e.join("\n")),
+            Self::EnvError(e) => format!("Error reading environment variables: {e}"),
+            Self::InvalidExtends(e) => format!("Error extending config files: {e}"),
         };
         write!(f, "{text}")
     }
@@ -35,6 +69,18 @@ impl From<std::io::Error> for ConfigParseError {
     }
 }
 
+impl From<VariableError> for ConfigParseError {
+    fn from(value: VariableError) -> Self {
+        Self::VariableError(value)
+    }
+}
+
+impl From<json5::Error> for ConfigParseError {
+    fn from(value: json5::Error) -> Self {
+        Self::DevContainerError(value)
+    }
+}
+
 impl From<toml::de::Error> for ConfigParseError {
     fn from(value: toml::de::Error) -> Self {
         Self::DeserializationError(value)
@@ -53,56 +99,232 @@ pub struct ProxyConfig {
     pub patch_pid: Option<Vec<String>>,
     pub log_level: String,
     pub use_docker: bool,
+
+    /// Additional host ↔ container mappings, `local_path` → `docker_internal_path` is always
+    /// the root mapping and is not part of this list
+    pub mappings: Vec<PathMapping>,
+
+    /// Indicates that `docker_internal_path` was not provided, then the root and the mappings
+    /// are derived from the container mounts at startup
+    pub auto_mappings: bool,
+
+    /// Indicates that `container` was not provided, then the container is discovered at startup
+    /// from the running containers that mount the local path
+    pub auto_container: bool,
+
+    /// Program spawned for the LSP; it is the `executable` unless the `[servers.<name>]` table
+    /// points to another one
+    pub command: String,
+    /// Default arguments for the LSP, used when no arguments are passed after `--`
+    pub args: Vec<String>,
+    /// Environment variables for the LSP
+    pub env: BTreeMap<String, String>,
+
+    /// Directories where Docker is used, and the ones where it is not, as configured; they
+    /// decide `use_docker` with the config files outside the project
+    pub pattern: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Files that indicate the root of a project, for `$PROJECT_ROOT`
+    pub project_markers: Vec<String>,
+    /// User-defined variables, before their expansion
+    pub variables: BTreeMap<String, String>,
+
+    /// The rule that decided `use_docker`
+    pub docker_reason: String,
+    /// Where the value of each field comes from
+    pub sources: Sources,
+    /// Problems of the configuration that do not stop the LSP, they are logged at startup
+    pub warnings: Vec<String>,
+
+    /// Compose service whose running container is used, it has precedence over `container`
+    pub compose: Option<ComposeService>,
+
+    /// User of `docker exec`
+    pub user: Option<String>,
+    /// Container runtime, `auto` is already resolved
+    pub runtime: Runtime,
+    /// Command template that runs a program in the environment, it replaces the runtime
+    pub launcher: Option<Vec<String>>,
+    /// Pod selection of the `kubernetes` runtime
+    pub kubernetes: Kubernetes,
+    /// Local workspace folder of a `devcontainer.json`, its container is found by the
+    /// `devcontainer.local_folder` label
+    pub devcontainer_folder: Option<String>,
+}
+
+/// A service of a Docker Compose project, resolved to a container by the compose labels
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComposeService {
+    pub service: String,
+    /// Project name, as passed to `docker compose -p`
+    pub project: Option<String>,
+    /// Compose file of the project, relative paths are resolved from the local path
+    pub file: Option<String>,
 }
 
 impl ProxyConfig {
-    pub fn from_file(
-        config_path: Option<&ConfigPath>,
-        cli: &mut Cli,
-    ) -> Result<Self, ConfigParseError> {
+    /// Build the config merging the files field by field, from the lowest to the highest
+    /// precedence; the CLI arguments have the final precedence
+    pub fn from_file(config_paths: &[ConfigPath], cli: &mut Cli) -> Result<Self, ConfigParseError> {
+        let cwd = current_dir()?;
+        // `lspdock config show` doesn't spawn the LSP, the fields of the server are shown empty
+        let optional_executable = cli.command.is_some();
         let mut config = ProxyConfigToml::default();
-        if let Some(cp) = config_path {
-            let file_str = std::fs::read_to_string(&cp.path)?;
-            config = toml::from_str(&file_str)?;
+        let mut profile_decision = None;
+        let mut interpolator = Interpolator::builtin();
+        let mut sources = Sources::default();
+        let mut warnings = vec![];
+
+        for cp in config_paths {
+            // The files extended by a config file are layers below it
+            let layers = match cp.r#type {
+                PathType::DevContainer => {
+                    let file_str = std::fs::read_to_string(&cp.path)?;
+                    vec![(cp.path.clone(), parse_devcontainer(&file_str, &cp.path)?)]
+                }
+                _ => load_with_extends(&cp.path)?,
+            };
+
+            for (path, mut layer) in layers {
+                let raw = layer.field_values();
+                interpolator.expand_config(&mut layer)?;
+                let expanded = layer.field_values();
+                warnings.extend(
+                    layer
+                        .relative_rules()
+                        .map(|warning| format!("{}: {warning}", path.display())),
+                );
+
+                // A matching profile overrides the top-level fields of the file
+                if let Some(decision) = layer.apply_profile(&cwd)? {
+                    profile_decision = Some(decision);
+                }
+                sources.record_layer(
+                    &raw,
+                    &expanded,
+                    &layer.field_values(),
+                    Source::File(path.clone()),
+                    Source::Profile(path),
+                );
+                config.merge(layer);
+            }
+        }
+
+        // The environment variables are between the files and the CLI
+        let vars = std::env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)));
+        let (env_layers, env_warnings) = env_layers(vars).map_err(ConfigParseError::EnvError)?;
+        warnings.extend(env_warnings);
+        for (var, mut layer) in env_layers {
+            let raw = layer.field_values();
+            interpolator.expand_config(&mut layer)?;
+            let expanded = layer.field_values();
+            warnings.extend(
+                layer
+                    .relative_rules()
+                    .map(|warning| format!("{var}: {warning}")),
+            );
+            let source = Source::Env(var);
+            sources.record_layer(&raw, &expanded, &expanded, source.clone(), source);
+            config.merge(layer);
         }
 
         // Cli has precedence in priority
-        config.container = cli.container.take().or(config.container);
-        config.local_path = cli.local_path.take().or(config.local_path);
-        config.docker_internal_path = cli.docker_path.take().or(config.docker_internal_path);
-        config.executable = cli.exec.take().or(config.executable);
-        config.pattern = cli.pattern.take().or(config.pattern);
-        config.patch_pid = cli.pids.take().or(config.patch_pid);
-        config.log_level = cli.log_level.take().or(config.log_level);
-
-        let cwd_var = VariableCwd::default();
-        let parent_var = VariableParent::default();
-        let home_var = VariableHome::default();
-        cwd_var.expand(&mut config).unwrap();
-        parent_var.expand(&mut config).unwrap();
-        home_var.expand(&mut config).unwrap();
-
-        let use_docker = match config_path {
-            Some(cp) => match cp.r#type {
-                PathType::Home => {
-                    let cwd = current_dir()?;
-                    cwd_matches_pattern(&cwd, config.pattern.as_deref())
-                }
-                PathType::Cwd => true, // In cwd always the pattern matches
-            },
-            None => true,
+        let mut cli_config = ProxyConfigToml::from(cli);
+        let raw = cli_config.field_values();
+        interpolator.expand_config(&mut cli_config)?;
+        let expanded = cli_config.field_values();
+        sources.record_layer(&raw, &expanded, &expanded, Source::Cli, Source::Cli);
+        warnings.extend(
+            cli_config
+                .relative_rules()
+                .map(|warning| format!("--pattern: {warning}")),
+        );
+        config.merge(cli_config);
+
+        let invalid = invalid_values(&config);
+        if !invalid.is_empty() {
+            return Err(ConfigParseError::InvalidValues(invalid));
+        }
+
+        if optional_executable {
+            config.executable.get_or_insert_default();
+        }
+
+        let project_path = config_paths
+            .iter()
+            .find(|cp| matches!(cp.r#type, PathType::Cwd | PathType::DevContainer));
+
+        let (use_docker, docker_reason) = if let Some(cp) = project_path {
+            // In cwd always the pattern matches
+            (true, format!("project config {} found", cp.path.display()))
+        } else if let Some(decision) = profile_decision {
+            // The top-level exclude of every file applies to the profiles too
+            let exclude = config.exclude.as_ref().map(MergeList::as_slice);
+            match match_cwd(&cwd, &[], exclude.unwrap_or_default())
+                .map_err(ConfigParseError::InvalidPattern)?
+            {
+                excluded @ Decision::Excluded(_) => (false, excluded.to_string()),
+                _ => (true, format!("profile {decision}")),
+            }
+        } else if !config_paths.is_empty() {
+            let decision = match_cwd(
+                &cwd,
+                config
+                    .pattern
+                    .as_ref()
+                    .map(MergeList::as_slice)
+                    .unwrap_or_default(),
+                config
+                    .exclude
+                    .as_ref()
+                    .map(MergeList::as_slice)
+                    .unwrap_or_default(),
+            )
+            .map_err(ConfigParseError::InvalidPattern)?;
+            (decision.matches(), decision.to_string())
+        } else if config.container.is_some()
+            || config.compose_service.is_some()
+            || config.launcher.is_some()
+            || config
+                .kubernetes
+                .as_ref()
+                .is_some_and(|k| k.selector.is_some())
+        {
+            (true, "container provided without a config file".into())
+        } else {
+            // Without a config file, Docker is used only if the container is provided
+            (false, "no config file and no container".into())
         };
 
-        Self::from_proxy_config_toml(config, use_docker)
+        let mut proxy_config = Self::from_proxy_config_toml(config, use_docker)?;
+        proxy_config.docker_reason = docker_reason;
+        proxy_config.warnings = warnings;
+
+        // The derived values override the layers, e.g. the binary name or a server table
+        sources.extend(std::mem::take(&mut proxy_config.sources));
+        proxy_config.sources = sources;
+
+        Ok(proxy_config)
     }
 
     pub fn from_proxy_config_toml(
-        config: ProxyConfigToml,
-        mut use_docker: bool,
+        mut config: ProxyConfigToml,
+        use_docker: bool,
     ) -> Result<Self, ConfigParseError> {
+        let mut sources = Sources::default();
+        if config.local_path.is_none() {
+            sources.set(
+                "local_path",
+                Source::Derived("current directory".into()),
+                None,
+            );
+        }
+
         #[allow(unused_mut)]
         let local_path = config
             .local_path
+            .take()
             .or_else(|| current_dir().ok()?.to_str().map(String::from));
 
         // Normalize local path for Windows
@@ -118,31 +340,144 @@ impl ProxyConfig {
         if executable == "lspdock" {
             executable = config
                 .executable
+                .take()
                 .ok_or(ConfigParseError::MissingField("executable"))?;
+        } else {
+            sources.set("executable", Source::Derived("binary name".into()), None);
         }
 
-        // Auto-disable Docker if required fields missing (zero-config mode)
-        if config.container.is_none() || config.docker_internal_path.is_none() {
-            use_docker = false;
+        let (runtime, found) = config.runtime.unwrap_or_default().resolve();
+        if config.runtime.is_none_or(|r| r == Runtime::Auto) {
+            let from = if found {
+                format!("{} found in PATH", runtime.program())
+            } else {
+                format!("neither docker nor podman found in PATH, defaulting to {runtime}")
+            };
+            sources.set("runtime", Source::Derived(from), None);
         }
 
+        let server = config.apply_server(&executable);
+        let server_source = Source::Derived(format!("[servers.{executable}]"));
+        if server.container.is_some() {
+            sources.set("container", server_source.clone(), None);
+        }
+        if server.compose_service.is_some() {
+            sources.set("compose_service", server_source.clone(), None);
+        }
+        if server.patch_pid.is_some() {
+            sources.set("patch_pid", server_source.clone(), None);
+        }
+        for (name, provided) in [
+            ("command", server.executable.is_some()),
+            ("args", server.args.is_some()),
+            ("env", !server.env.is_empty()),
+        ] {
+            if provided {
+                sources.set(name, server_source.clone(), None);
+            }
+        }
+        let command = server.executable.unwrap_or_else(|| {
+            sources.set("command", Source::Derived("executable".into()), None);
+            executable.clone()
+        });
+        let default_markers = DEFAULT_PROJECT_MARKERS.iter().map(|m| m.to_string());
+        let project_markers = MergeList::merge(
+            Some(MergeList::Replace(default_markers.collect())),
+            config.project_markers.take(),
+        )
+        .map(MergeList::into_vec)
+        .unwrap_or_default();
+
+        let compose = config.compose_service.take().map(|service| ComposeService {
+            service,
+            project: config.compose_project.take(),
+            file: config.compose_file.take(),
+        });
+
+        // Without the container, it is discovered from the running containers; a launcher
+        // cannot list the environments, then the container is optional
+        let launcher = config.launcher.take();
+        let kubernetes = config.kubernetes.take().unwrap_or_default();
+        let auto_container = config.container.is_none()
+            && compose.is_none()
+            && config.devcontainer_folder.is_none()
+            && launcher.is_none()
+            && runtime.has_containers();
+
+        // Without the internal path, it is derived from the container mounts. The environments
+        // of a launcher usually share the filesystem of the host.
+        if launcher.is_some() && config.docker_internal_path.is_none() {
+            config.docker_internal_path = Some(local_path.clone());
+            sources.set(
+                "docker_internal_path",
+                Source::Derived("local_path, with a launcher".into()),
+                None,
+            );
+        }
+        let auto_mappings = config.docker_internal_path.is_none();
+
         // Use empty strings as placeholders when Docker is disabled
         let container = config.container.unwrap_or_default();
         let docker_internal_path = config.docker_internal_path.unwrap_or_default();
 
+        #[allow(unused_mut)]
+        let mut mappings = config.mappings.map(MergeList::into_vec).unwrap_or_default();
+
+        // Normalize local paths for Windows
+        #[cfg(windows)]
+        for mapping in &mut mappings {
+            mapping.local_path = normalize_win_local(&mapping.local_path);
+        }
+
         Ok(Self {
             container,
             docker_internal_path,
             local_path: local_path.clone(),
             executable,
-            patch_pid: config.patch_pid,
-            log_level: config
-                .log_level
-                .unwrap_or_else(|| std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into())),
+            patch_pid: config.patch_pid.map(MergeList::into_vec),
+            log_level: config.log_level.unwrap_or_else(|| {
+                std::env::var("RUST_LOG")
+                    .inspect(|_| sources.set("log_level", Source::Derived("RUST_LOG".into()), None))
+                    .unwrap_or_else(|_| "info".into())
+            }),
             use_docker,
+            mappings,
+            auto_mappings,
+            auto_container,
+            command,
+            args: server.args.unwrap_or_default(),
+            env: server.env,
+            pattern: config.pattern.map(MergeList::into_vec).unwrap_or_default(),
+            exclude: config.exclude.map(MergeList::into_vec).unwrap_or_default(),
+            project_markers,
+            variables: config.variables,
+            docker_reason: String::new(),
+            sources,
+            warnings: vec![],
+            compose,
+            user: config.user,
+            runtime,
+            launcher,
+            kubernetes,
+            devcontainer_folder: config.devcontainer_folder,
         })
     }
 
+    /// Iterate over every `(local_path, docker_internal_path)` pair, starting with the root mapping
+    pub fn path_mappings(&self) -> impl Iterator<Item = (&str, &str)> {
+        std::iter::once((self.local_path.as_str(), self.docker_internal_path.as_str())).chain(
+            self.mappings
+                .iter()
+                .map(|m| (m.local_path.as_str(), m.docker_internal_path.as_str())),
+        )
+    }
+
+    /// Indicate if the uri points to a path that is available in the host
+    pub fn is_local_uri(&self, uri: &str) -> bool {
+        self.path_mappings()
+            .any(|(local, _)| !local.is_empty() && uri.contains(local))
+    }
+
     /// Indicate if the executable requires patch to the pid
     pub fn requires_patch_pid(&self) -> bool {
         match &self.patch_pid {
@@ -159,20 +494,498 @@ impl ProxyConfig {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+/// Configuration of lspdock, written in `lspdock.toml`
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default)]
+#[schemars(deny_unknown_fields, title = "lspdock.toml")]
 pub struct ProxyConfigToml {
+    /// Config files that this file extends, they are loaded first and this file overrides them;
+    /// relative paths are resolved against the directory of this file
+    #[serde(default, deserialize_with = "string_or_list")]
+    #[schemars(with = "Option<OneOrMany>")]
+    pub(super) extends: Option<MergeList>,
+    /// Name of the Docker container, or the host of the `ssh` runtime; if it is omitted, the
+    /// running container that mounts the project directory is used
     pub(super) container: Option<String>,
+    /// Path of the project inside the container; if it is omitted, it is derived from the
+    /// container mounts
     pub(super) docker_internal_path: Option<String>,
+    /// Path of the project in the host, by default the current working directory
     pub(super) local_path: Option<String>,
+    /// Executable of the LSP, used when the lspdock binary is not renamed and `--exec` is not
+    /// passed
     pub(super) executable: Option<String>,
+    /// Docker Compose service, the container is resolved from the running containers of the
+    /// service instead of using `container`
+    pub(super) compose_service: Option<String>,
+    /// Compose project of the service, by default the project whose working directory
+    /// contains the local path
+    pub(super) compose_project: Option<String>,
+    /// Compose file of the project, used to tell apart projects with the same service names
+    pub(super) compose_file: Option<String>,
+    /// User of `docker exec`, by default the user of the container
+    pub(super) user: Option<String>,
+    /// Container runtime: `docker`, `podman`, `kubernetes`, `ssh` or `auto`, by default the first one
+    /// installed of Docker and Podman
+    #[schemars(extend("default" = "auto"))]
+    pub(super) runtime: Option<Runtime>,
+    /// Pod of the `kubernetes` runtime; `container` is the pod name, or it is selected by the
+    /// labels
+    pub(super) kubernetes: Option<Kubernetes>,
+    /// Command that runs a program in an environment that is not a container of the runtime,
+    /// e.g. `["distrobox", "enter", "{container}", "--", "{executable}"]`; the placeholders are
+    /// `{container}`, `{workdir}` and `{executable}`
+    pub(super) launcher: Option<Vec<String>>,
+    /// Set by a `devcontainer.json` file, it is not part of the TOML config
+    #[serde(skip)]
+    pub(super) devcontainer_folder: Option<String>,
     /// This serves as a pattern for the proxy to Docker; if the pattern doesn't match, the proxy will
-    /// forward requests directly to the local LSP.
-    pub(super) pattern: Option<String>,
+    /// forward requests directly to the local LSP. It can be a single entry or a list of globs
+    /// or `regex:` entries.
+    #[serde(default, deserialize_with = "string_or_list")]
+    #[schemars(with = "Option<OneOrMany>")]
+    pub(super) pattern: Option<MergeList>,
+    /// Directories where Docker is not used even if the pattern matches, also for the profiles
+    #[serde(default, deserialize_with = "string_or_list")]
+    #[schemars(with = "Option<OneOrMany>")]
+    pub(super) exclude: Option<MergeList>,
 
     /// Indicates whether to patch the PID to null; this is used when the LSP tries to track the IDE and
     /// auto-kill when it can't detect it. The listed executables in this list will be patched
-    pub(super) patch_pid: Option<Vec<String>>,
+    pub(super) patch_pid: Option<MergeList>,
+    /// Log level, written as a `tracing` filter, e.g. `debug` or `lspdock=debug,info`; by default
+    /// `RUST_LOG` or `info`
+    #[schemars(extend("default" = "info", "examples" = ["trace", "debug", "info", "warn", "error"]))]
     pub(super) log_level: Option<String>,
+    /// Files that indicate the root of a project, used by `$PROJECT_ROOT`
+    pub(super) project_markers: Option<MergeList>,
+    /// User-defined variables, they can reference the built-in variables and each other
+    #[serde(default)]
+    pub(super) variables: BTreeMap<String, String>,
+
+    /// Per-project profiles; the first profile whose pattern matches the current working directory
+    /// overrides the top-level fields.
+    #[serde(default, rename = "profile")]
+    pub(super) profiles: Vec<ProfileToml>,
+
+    /// Additional host ↔ container mappings, used when several host directories are mounted
+    /// in the container
+    pub(super) mappings: Option<MergeList<PathMapping>>,
+
+    /// Per-language-server settings, keyed by the resolved executable name
+    #[serde(default)]
+    pub(super) servers: HashMap<String, ServerToml>,
+}
+
+/// A `[servers.<name>]` table, it holds the settings of a single language server
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default)]
+#[schemars(deny_unknown_fields)]
+pub struct ServerToml {
+    /// The real executable, e.g. a `pylsp` shim can point to `python` with `args = ["-m", "pylsp"]`
+    pub(super) executable: Option<String>,
+    /// Default arguments, used when no arguments are passed after `--`
+    pub(super) args: Option<Vec<String>>,
+    /// Environment variables of the LSP
+    #[serde(default)]
+    pub(super) env: BTreeMap<String, String>,
+    /// Patch the PID of this server, overrides the top-level `patch_pid` list
+    pub(super) patch_pid: Option<bool>,
+    /// Container for this server, overrides the top-level `container`
+    pub(super) container: Option<String>,
+    /// Compose service for this server, overrides the top-level `compose_service`
+    pub(super) compose_service: Option<String>,
+}
+
+impl ServerToml {
+    /// Override the fields with the ones present in `other`, the `env` variables are added
+    fn merge(&mut self, other: ServerToml) {
+        self.executable = other.executable.or(self.executable.take());
+        self.args = other.args.or(self.args.take());
+        self.env.extend(other.env);
+        self.patch_pid = other.patch_pid.or(self.patch_pid.take());
+        self.container = other.container.or(self.container.take());
+        self.compose_service = other.compose_service.or(self.compose_service.take());
+    }
+
+    /// Every string value of the table, except the container
+    pub(super) fn strings_mut(&mut self) -> Vec<&mut String> {
+        let mut fields: Vec<&mut String> = [&mut self.executable, &mut self.compose_service]
+            .into_iter()
+            .flatten()
+            .collect();
+        fields.extend(self.args.iter_mut().flatten());
+        fields.extend(self.env.values_mut());
+        fields
+    }
+}
+
+/// A `[[mappings]]` table, it maps a host path to a container path
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct PathMapping {
+    /// Path in the host
+    pub local_path: String,
+    /// Path inside the container
+    pub docker_internal_path: String,
+}
+
+/// A `[[profile]]` table, it holds the settings of a single project; the fields override the
+/// top-level fields
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default)]
+#[schemars(deny_unknown_fields)]
+pub struct ProfileToml {
+    /// Directories of the project, the profile is applied when the current working directory
+    /// matches
+    #[serde(default, deserialize_with = "string_or_list")]
+    #[schemars(with = "Option<OneOrMany>")]
+    pub(super) pattern: Option<MergeList>,
+    /// Directories where the profile is not applied even if the pattern matches, they are
+    /// added to the top-level `exclude`
+    #[serde(default, deserialize_with = "string_or_list")]
+    #[schemars(with = "Option<OneOrMany>")]
+    pub(super) exclude: Option<MergeList>,
+    /// Name of the Docker container of the project
+    pub(super) container: Option<String>,
+    /// Compose service of the project, its running container is used
+    pub(super) compose_service: Option<String>,
+    /// Compose project of the service, as passed to `docker compose -p`
+    pub(super) compose_project: Option<String>,
+    /// Compose file of the project, relative to the local path
+    pub(super) compose_file: Option<String>,
+    /// Path of the project inside the container
+    pub(super) docker_internal_path: Option<String>,
+    /// Path of the project on the host
+    pub(super) local_path: Option<String>,
+    /// Executable of the LSP server
+    pub(super) executable: Option<String>,
+    /// LSP servers that require the PID patch, see the top-level `patch_pid`
+    pub(super) patch_pid: Option<MergeList>,
+}
+
+impl ProfileToml {
+    /// Every string value of the table, except the container
+    pub(super) fn strings_mut(&mut self) -> Vec<&mut String> {
+        let mut fields: Vec<&mut String> = [
+            &mut self.compose_service,
+            &mut self.compose_project,
+            &mut self.compose_file,
+            &mut self.docker_internal_path,
+            &mut self.local_path,
+            &mut self.executable,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        fields.extend(self.pattern.iter_mut().flat_map(MergeList::values_mut));
+        fields.extend(self.exclude.iter_mut().flat_map(MergeList::values_mut));
+        fields.extend(self.patch_pid.iter_mut().flat_map(MergeList::values_mut));
+        fields
+    }
+}
+
+/// A list that replaces the list of the previous configuration layer, or is appended
+/// to it when written as `{ append = [...] }`
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+#[schemars(rename = "MergeList_of_{T}")]
+pub enum MergeList<T = String> {
+    /// Replace the list of the previous layers
+    Replace(Vec<T>),
+    /// Append to the list of the previous layers
+    Append { append: Vec<T> },
+}
+
+impl<T> MergeList<T> {
+    pub fn into_vec(self) -> Vec<T> {
+        match self {
+            Self::Replace(values) | Self::Append { append: values } => values,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            Self::Replace(values) | Self::Append { append: values } => values,
+        }
+    }
+
+    pub fn values_mut(&mut self) -> &mut Vec<T> {
+        match self {
+            Self::Replace(values) | Self::Append { append: values } => values,
+        }
+    }
+
+    /// Merge `next` over `previous` following the semantics of `next`
+    pub(super) fn merge(previous: Option<Self>, next: Option<Self>) -> Option<Self> {
+        match (previous, next) {
+            (previous, None) => previous,
+            (_, Some(Self::Replace(values))) => Some(Self::Replace(values)),
+            (None, Some(append)) => Some(append),
+            (Some(Self::Replace(mut values)), Some(Self::Append { append })) => {
+                values.extend(append);
+                Some(Self::Replace(values))
+            }
+            (Some(Self::Append { append: mut values }), Some(Self::Append { append })) => {
+                values.extend(append);
+                Some(Self::Append { append: values })
+            }
+        }
+    }
+}
+
+impl ProxyConfigToml {
+    /// The top-level fields that are tracked by the provenance, rendered as strings
+    pub(super) fn field_values(&self) -> FieldValues {
+        vec![
+            ("container", self.container.clone()),
+            ("compose_service", self.compose_service.clone()),
+            ("compose_project", self.compose_project.clone()),
+            ("compose_file", self.compose_file.clone()),
+            ("user", self.user.clone()),
+            ("runtime", self.runtime.map(|r| r.to_string())),
+            ("launcher", self.launcher.as_ref().map(|l| l.join(" "))),
+            (
+                "kubernetes",
+                self.kubernetes.as_ref().map(Kubernetes::to_string),
+            ),
+            ("docker_internal_path", self.docker_internal_path.clone()),
+            ("local_path", self.local_path.clone()),
+            ("executable", self.executable.clone()),
+            (
+                "patch_pid",
+                self.patch_pid.clone().map(|p| p.into_vec().join(", ")),
+            ),
+            ("log_level", self.log_level.clone()),
+            (
+                "pattern",
+                self.pattern.as_ref().map(|p| p.as_slice().join(", ")),
+            ),
+            (
+                "exclude",
+                self.exclude.as_ref().map(|e| e.as_slice().join(", ")),
+            ),
+            (
+                "project_markers",
+                self.project_markers
+                    .as_ref()
+                    .map(|m| m.as_slice().join(", ")),
+            ),
+            (
+                "variables",
+                (!self.variables.is_empty()).then(|| display_env(&self.variables)),
+            ),
+            (
+                "mappings",
+                self.mappings
+                    .as_ref()
+                    .map(|m| display_mappings(m.as_slice())),
+            ),
+        ]
+    }
+
+    /// Every top-level string value of the config, including the lists. The `[[profile]]`
+    /// and `[servers.<name>]` tables are expanded on their own, with their container
+    pub(super) fn strings_mut(&mut self) -> Vec<&mut String> {
+        let mut fields: Vec<&mut String> = [
+            &mut self.container,
+            &mut self.compose_service,
+            &mut self.compose_project,
+            &mut self.compose_file,
+            &mut self.user,
+            &mut self.devcontainer_folder,
+            &mut self.docker_internal_path,
+            &mut self.local_path,
+            &mut self.executable,
+            &mut self.log_level,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        fields.extend(self.pattern.iter_mut().flat_map(MergeList::values_mut));
+        fields.extend(self.exclude.iter_mut().flat_map(MergeList::values_mut));
+        fields.extend(self.launcher.iter_mut().flatten());
+        fields.extend(self.kubernetes.iter_mut().flat_map(Kubernetes::strings_mut));
+
+        fields.extend(self.patch_pid.iter_mut().flat_map(MergeList::values_mut));
+        fields.extend(
+            self.project_markers
+                .iter_mut()
+                .flat_map(MergeList::values_mut),
+        );
+
+        for mapping in self.mappings.iter_mut().flat_map(MergeList::values_mut) {
+            fields.push(&mut mapping.local_path);
+            fields.push(&mut mapping.docker_internal_path);
+        }
+
+        fields
+    }
+
+    /// Warnings of the `pattern` and `exclude` entries that never match, of the top-level
+    /// and of the profiles
+    fn relative_rules(&self) -> impl Iterator<Item = String> + '_ {
+        let top = [&self.pattern, &self.exclude];
+        let profiles = self.profiles.iter().flat_map(|p| [&p.pattern, &p.exclude]);
+
+        top.into_iter()
+            .chain(profiles)
+            .flatten()
+            .flat_map(MergeList::as_slice)
+            .filter_map(|rule| relative_rule_warning(rule))
+    }
+
+    /// Override the fields with the first profile that matches the `cwd`. Returns the rule
+    /// that matched if a profile has been applied. A profile without a pattern never matches,
+    /// and the top-level `exclude` applies to every profile.
+    fn apply_profile(&mut self, cwd: &Path) -> Result<Option<Decision>, ConfigParseError> {
+        let top_exclude = self.exclude.as_ref().map(MergeList::as_slice);
+
+        let mut matched = None;
+        for (index, profile) in self.profiles.iter().enumerate() {
+            let pattern = profile.pattern.as_ref().map(MergeList::as_slice);
+            if pattern.unwrap_or_default().iter().all(|p| p.is_empty()) {
+                continue;
+            }
+
+            let exclude: Vec<String> = top_exclude
+                .into_iter()
+                .chain(profile.exclude.as_ref().map(MergeList::as_slice))
+                .flatten()
+                .cloned()
+                .collect();
+            let decision = match_cwd(cwd, pattern.unwrap_or_default(), &exclude)
+                .map_err(ConfigParseError::InvalidPattern)?;
+
+            if decision.matches() {
+                matched = Some((index, decision));
+                break;
+            }
+        }
+
+        let Some((index, decision)) = matched else {
+            return Ok(None);
+        };
+
+        let profile = self.profiles.swap_remove(index);
+        self.pattern = profile.pattern.or(self.pattern.take());
+        // The exclude of the profile is added to the top-level one
+        let exclude = profile.exclude.map(|e| MergeList::Append {
+            append: e.into_vec(),
+        });
+        self.exclude = MergeList::merge(self.exclude.take(), exclude);
+        self.container = profile.container.or(self.container.take());
+        self.compose_service = profile.compose_service.or(self.compose_service.take());
+        self.compose_project = profile.compose_project.or(self.compose_project.take());
+        self.compose_file = profile.compose_file.or(self.compose_file.take());
+        self.docker_internal_path = profile
+            .docker_internal_path
+            .or(self.docker_internal_path.take());
+        self.local_path = profile.local_path.or(self.local_path.take());
+        self.executable = profile.executable.or(self.executable.take());
+        self.patch_pid = MergeList::merge(self.patch_pid.take(), profile.patch_pid);
+
+        Ok(Some(decision))
+    }
+
+    /// Apply the `[servers.<name>]` table of the executable to the top-level fields and
+    /// return it; an empty table is returned if the server is not configured
+    fn apply_server(&mut self, name: &str) -> ServerToml {
+        let Some(server) = self.servers.remove(name) else {
+            return ServerToml::default();
+        };
+
+        // The container of the server is more specific than the compose service of the file
+        if server.container.is_some() {
+            self.compose_service = None;
+        }
+        self.container = server.container.clone().or(self.container.take());
+        self.compose_service = server
+            .compose_service
+            .clone()
+            .or(self.compose_service.take());
+
+        if let Some(patch) = server.patch_pid {
+            let patch_pid = self
+                .patch_pid
+                .get_or_insert_with(|| MergeList::Replace(vec![]))
+                .values_mut();
+            patch_pid.retain(|p| p != name);
+            if patch {
+                patch_pid.push(name.to_string());
+            }
+        }
+
+        server
+    }
+
+    /// Override the fields with the ones present in `other`
+    fn merge(&mut self, other: ProxyConfigToml) {
+        self.container = other.container.or(self.container.take());
+        self.compose_service = other.compose_service.or(self.compose_service.take());
+        self.compose_project = other.compose_project.or(self.compose_project.take());
+        self.compose_file = other.compose_file.or(self.compose_file.take());
+        self.user = other.user.or(self.user.take());
+        self.runtime = other.runtime.or(self.runtime);
+        self.launcher = other.launcher.or(self.launcher.take());
+        self.kubernetes = match (self.kubernetes.take(), other.kubernetes) {
+            (Some(mut kubernetes), Some(other)) => {
+                kubernetes.merge(other);
+                Some(kubernetes)
+            }
+            (kubernetes, other) => other.or(kubernetes),
+        };
+        self.devcontainer_folder = other
+            .devcontainer_folder
+            .or(self.devcontainer_folder.take());
+        self.local_path = other.local_path.or(self.local_path.take());
+        self.docker_internal_path = other
+            .docker_internal_path
+            .or(self.docker_internal_path.take());
+        self.executable = other.executable.or(self.executable.take());
+        self.pattern = MergeList::merge(self.pattern.take(), other.pattern);
+        self.exclude = MergeList::merge(self.exclude.take(), other.exclude);
+        self.patch_pid = MergeList::merge(self.patch_pid.take(), other.patch_pid);
+        self.log_level = other.log_level.or(self.log_level.take());
+        self.project_markers = MergeList::merge(self.project_markers.take(), other.project_markers);
+        self.mappings = MergeList::merge(self.mappings.take(), other.mappings);
+        for (name, server) in other.servers {
+            self.servers.entry(name).or_default().merge(server);
+        }
+        self.variables.extend(other.variables);
+    }
+}
+
+impl From<&mut Cli> for ProxyConfigToml {
+    fn from(cli: &mut Cli) -> Self {
+        Self {
+            container: cli.container.take(),
+            docker_internal_path: cli.docker_path.take(),
+            local_path: cli.local_path.take(),
+            executable: cli.exec.take(),
+            pattern: cli.pattern.take().map(|p| MergeList::Replace(vec![p])),
+            patch_pid: cli.pids.take().map(MergeList::Replace),
+            log_level: cli.log_level.take(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Render the mappings as `local → internal` pairs
+pub fn display_mappings(mappings: &[PathMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{} → {}", m.local_path, m.docker_internal_path))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render a map as `key=value` pairs
+pub fn display_env(values: &BTreeMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 fn extract_binary_name(full_path: &str) -> String {
@@ -197,35 +1010,13 @@ fn normalize_win_local(path: &str) -> String {
     s
 }
 
-fn norm_for_match<S: AsRef<str>>(s: S) -> String {
-    #[allow(unused_mut)]
-    let mut t = s.as_ref().replace('\\', "/");
-
-    #[cfg(windows)]
-    {
-        t.make_ascii_lowercase();
-    }
-
-    t
-}
-
-fn cwd_matches_pattern(cwd: &Path, pattern: Option<&str>) -> bool {
-    let cwd_s = norm_for_match(cwd.to_string_lossy());
-    match pattern {
-        Some(p) if !p.is_empty() => {
-            let p = norm_for_match(p);
-            cwd_s.contains(&p)
-        }
-        _ => true, // no pattern → default to Docker
-    }
-}
-
 #[allow(dead_code)] // Not used in Unix
 pub fn encode_path(msg: &Bytes, config: &mut ProxyConfig) {
-    config.local_path = if find(msg, b"%3A/").is_some() {
-        config.local_path.replace(":", "%3A")
-    } else {
-        config.local_path.clone()
+    if find(msg, b"%3A/").is_some() {
+        config.local_path = config.local_path.replace(":", "%3A");
+        for mapping in &mut config.mappings {
+            mapping.local_path = mapping.local_path.replace(":", "%3A");
+        }
     }
 }
 
@@ -252,6 +1043,268 @@ mod tests {
         let expect = "lspdock";
         assert_eq!(extract_binary_name(full_path), expect);
     }
+
+    #[test]
+    fn apply_first_matching_profile() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "default-web-1"
+            docker_internal_path = "/usr/src/app"
+            pattern = "/home/me/dev"
+
+            [[profile]]
+            pattern = "/home/me/dev/api"
+            container = "api-web-1"
+            patch_pid = ["pyright-langserver"]
+
+            [[profile]]
+            pattern = "/home/me/dev"
+            container = "other-web-1"
+            docker_internal_path = "/app"
+            "#,
+        )
+        .unwrap();
+
+        assert!(
+            config
+                .apply_profile(Path::new("/home/me/dev/api"))
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(config.container.as_deref(), Some("api-web-1"));
+        assert_eq!(config.docker_internal_path.as_deref(), Some("/usr/src/app"));
+        assert_eq!(
+            config.pattern,
+            Some(MergeList::Replace(vec!["/home/me/dev/api".into()]))
+        );
+        assert_eq!(
+            config.patch_pid,
+            Some(MergeList::Replace(vec!["pyright-langserver".into()]))
+        );
+    }
+
+    #[test]
+    fn apply_server_table() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "app-web-1"
+            patch_pid = ["pyright-langserver"]
+
+            [servers.pylsp]
+            executable = "python"
+            args = ["-m", "pylsp"]
+            env = { PYTHONPATH = "/usr/src/app" }
+            patch_pid = true
+            container = "app-tools-1"
+
+            [servers.pyright-langserver]
+            patch_pid = false
+            "#,
+        )
+        .unwrap();
+
+        let server = config.apply_server("pylsp");
+        assert_eq!(server.executable.as_deref(), Some("python"));
+        assert_eq!(server.args, Some(vec!["-m".into(), "pylsp".into()]));
+        assert_eq!(server.env.get("PYTHONPATH").unwrap(), "/usr/src/app");
+        assert_eq!(config.container.as_deref(), Some("app-tools-1"));
+        assert_eq!(
+            config.patch_pid.clone().map(MergeList::into_vec),
+            Some(vec!["pyright-langserver".into(), "pylsp".into()])
+        );
+
+        config.apply_server("pyright-langserver");
+        assert_eq!(
+            config.patch_pid.clone().map(MergeList::into_vec),
+            Some(vec!["pylsp".into()])
+        );
+
+        let server = config.apply_server("ruff");
+        assert!(server.executable.is_none());
+        assert_eq!(config.container.as_deref(), Some("app-tools-1"));
+    }
+
+    #[test]
+    fn merge_layers_field_by_field() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "app-web-1"
+            docker_internal_path = "/usr/src/app"
+            patch_pid = ["pyright-langserver"]
+
+            [[mappings]]
+            local_path = "/home/me/dev/libs"
+            docker_internal_path = "/usr/src/libs"
+
+            [servers.pyright-langserver]
+            container = "tools-1"
+            env = { NODE_OPTIONS = "--max-old-space-size=4096" }
+            "#,
+        )
+        .unwrap();
+
+        let layer: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "api-web-1"
+            patch_pid = { append = ["pylsp"] }
+            mappings = { append = [{ local_path = "/home/me/dev/vendor", docker_internal_path = "/vendor" }] }
+
+            [servers.pyright-langserver]
+            args = ["--stdio"]
+            env = { PYTHONPATH = "/opt/lib" }
+            "#,
+        )
+        .unwrap();
+        config.merge(layer);
+
+        assert_eq!(config.container.as_deref(), Some("api-web-1"));
+        let mappings = config.mappings.clone().map(MergeList::into_vec).unwrap();
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[1].docker_internal_path, "/vendor");
+
+        // The server table of the project keeps the fields of the home file
+        let server = &config.servers["pyright-langserver"];
+        assert_eq!(server.container.as_deref(), Some("tools-1"));
+        assert_eq!(server.args, Some(vec!["--stdio".to_string()]));
+        assert_eq!(
+            server.env.keys().collect::<Vec<_>>(),
+            ["NODE_OPTIONS", "PYTHONPATH"]
+        );
+        assert_eq!(config.docker_internal_path.as_deref(), Some("/usr/src/app"));
+        assert_eq!(
+            config.patch_pid,
+            Some(MergeList::Replace(vec![
+                "pyright-langserver".into(),
+                "pylsp".into()
+            ]))
+        );
+
+        let layer: ProxyConfigToml = toml::from_str(r#"patch_pid = ["ruff"]"#).unwrap();
+        config.merge(layer);
+        assert_eq!(
+            config.patch_pid,
+            Some(MergeList::Replace(vec!["ruff".into()]))
+        );
+    }
+
+    #[test]
+    fn fallback_to_top_level_without_profile() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "default-web-1"
+
+            [[profile]]
+            container = "any-web-1"
+
+            [[profile]]
+            pattern = "/home/me/dev/api"
+            container = "api-web-1"
+            "#,
+        )
+        .unwrap();
+
+        // The profile without a pattern does not hide the next ones
+        let mut api = config.clone();
+        assert!(
+            api.apply_profile(Path::new("/home/me/dev/api"))
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(api.container.as_deref(), Some("api-web-1"));
+
+        assert!(
+            config
+                .apply_profile(Path::new("/home/me/work"))
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(config.container.as_deref(), Some("default-web-1"));
+    }
+
+    #[test]
+    fn top_level_exclude_applies_to_profiles() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "default-web-1"
+            exclude = "/home/me/dev/sandbox"
+
+            [[profile]]
+            pattern = "/home/me/dev"
+            exclude = "/home/me/dev/tmp"
+            container = "dev-web-1"
+            "#,
+        )
+        .unwrap();
+
+        let cwd = Path::new("/home/me/dev/sandbox/x");
+        assert!(config.clone().apply_profile(cwd).unwrap().is_none());
+        let decision = match_cwd(
+            cwd,
+            config
+                .pattern
+                .as_ref()
+                .map(MergeList::as_slice)
+                .unwrap_or_default(),
+            config
+                .exclude
+                .as_ref()
+                .map(MergeList::as_slice)
+                .unwrap_or_default(),
+        )
+        .unwrap();
+        assert_eq!(decision, Decision::Excluded("/home/me/dev/sandbox".into()));
+        assert!(!decision.matches());
+
+        // The exclude of the profile is added to the top-level one
+        assert!(
+            config
+                .apply_profile(Path::new("/home/me/dev/api"))
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            config.exclude.map(MergeList::into_vec),
+            Some(vec![
+                "/home/me/dev/sandbox".into(),
+                "/home/me/dev/tmp".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn pattern_lists_merge_and_warn() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            pattern = "/home/me/dev"
+            exclude = ["/home/me/dev/sandbox"]
+            "#,
+        )
+        .unwrap();
+        let layer: ProxyConfigToml = toml::from_str(
+            r#"
+            pattern = { append = ["dev"] }
+            exclude = { append = ["/home/me/dev/tmp"] }
+            "#,
+        )
+        .unwrap();
+
+        let warnings: Vec<String> = layer.relative_rules().collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("`dev`"));
+
+        config.merge(layer);
+        assert_eq!(
+            config.pattern.map(MergeList::into_vec),
+            Some(vec!["/home/me/dev".into(), "dev".into()])
+        );
+        assert_eq!(
+            config.exclude.map(MergeList::into_vec),
+            Some(vec![
+                "/home/me/dev/sandbox".into(),
+                "/home/me/dev/tmp".into()
+            ])
+        );
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Container runtime that runs the LSP, written as `runtime`
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    /// Docker if it is installed, otherwise Podman
+    #[default]
+    Auto,
+    /// `docker exec` into a container
+    Docker,
+    /// `podman exec` into a container, including rootless Podman
+    Podman,
+    /// `kubectl exec` into a pod, see the `kubernetes` table
+    Kubernetes,
+    /// `ssh` to a remote host, the `container` is the host
+    Ssh,
+}
+
+/// The `[kubernetes]` table, it selects the pod of the `kubernetes` runtime
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct Kubernetes {
+    /// Namespace of the pod, by default the namespace of the kubectl context
+    pub namespace: Option<String>,
+    /// Label selector of the pod, e.g. `app=api`; it is used when `container` does not name the
+    /// pod
+    pub selector: Option<String>,
+    /// Container of the pod, by default the default container of the pod
+    pub container: Option<String>,
+}
+
+impl Kubernetes {
+    /// Override the fields with the ones present in `other`
+    pub(super) fn merge(&mut self, other: Self) {
+        self.namespace = other.namespace.or(self.namespace.take());
+        self.selector = other.selector.or(self.selector.take());
+        self.container = other.container.or(self.container.take());
+    }
+
+    pub(super) fn strings_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        [&mut self.namespace, &mut self.selector, &mut self.container]
+            .into_iter()
+            .flatten()
+    }
+}
+
+impl std::fmt::Display for Kubernetes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = [
+            ("namespace", &self.namespace),
+            ("selector", &self.selector),
+            ("container", &self.container),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{name}={}", value.as_ref()?)))
+        .collect();
+        write!(f, "{}", fields.join(", "))
+    }
+}
+
+impl Runtime {
+    /// Replace `auto` with the first runtime found in `PATH`, and tell whether it was found.
+    /// Docker is used when none is found, then the error of the missing program is reported
+    /// when it is used. The other runtimes are not looked up.
+    pub fn resolve(self) -> (Self, bool) {
+        match self {
+            Self::Auto => [Self::Docker, Self::Podman]
+                .into_iter()
+                .find(|runtime| in_path(runtime.program()))
+                .map_or((Self::Docker, false), |runtime| (runtime, true)),
+            runtime => (runtime, true),
+        }
+    }
+
+    /// Whether the runtime runs containers, which can be listed and inspected
+    pub fn has_containers(self) -> bool {
+        !matches!(self, Self::Kubernetes | Self::Ssh)
+    }
+
+    /// The CLI of the runtime
+    pub fn program(self) -> &'static str {
+        match self.resolve().0 {
+            Self::Podman => "podman",
+            Self::Kubernetes => "kubectl",
+            Self::Ssh => "ssh",
+            _ => "docker",
+        }
+    }
+}
+
+impl std::fmt::Display for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Kubernetes => write!(f, "kubernetes"),
+            runtime => write!(f, "{}", runtime.program()),
+        }
+    }
+}
+
+fn in_path(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(program);
+        is_executable(&candidate) || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
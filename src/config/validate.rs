@@ -0,0 +1,362 @@
+use std::{fmt::Display, path::Path};
+
+use serde::{
+    Deserialize, Deserializer,
+    de::{Error as _, Visitor, value::Error},
+    forward_to_deserialize_any,
+};
+use toml::de::{DeTable, DeValue};
+
+use super::provider::{MergeList, PathMapping, ProfileToml, ProxyConfigToml, ServerToml};
+use super::runtime::Kubernetes;
+
+/// A key of the config file that is not a field of the config
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownKey {
+    /// Dotted path of the key, e.g. `profile.docker_path`
+    pub key: String,
+    pub line: usize,
+    pub column: usize,
+    pub suggestion: Option<&'static str>,
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: unknown key `{}`",
+            self.line, self.column, self.key
+        )?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the keys of the TOML `content` that are not fields of the config, including the keys of
+/// the `[[profile]]`, `[[mappings]]` and `[servers.<name>]` tables
+pub(super) fn unknown_keys(content: &str) -> Vec<UnknownKey> {
+    unknown_keys_in(content, &[])
+}
+
+/// Like [`unknown_keys`], for a config embedded in the `section` table of the TOML `content`,
+/// e.g. `tool.lspdock` of `pyproject.toml`
+pub(super) fn unknown_keys_in(content: &str, section: &[&str]) -> Vec<UnknownKey> {
+    let Ok(document) = DeTable::parse(content) else {
+        // The syntax errors are reported by the deserialization
+        return vec![];
+    };
+
+    let mut root = document.get_ref();
+    for name in section {
+        match root.get(*name).map(|v| v.get_ref()) {
+            Some(DeValue::Table(table)) => root = table,
+            _ => return vec![],
+        }
+    }
+    let section: String = section.iter().map(|name| format!("{name}.")).collect();
+
+    let mut unknown = vec![];
+    check_table(
+        content,
+        &section,
+        root,
+        fields_of::<ProxyConfigToml>(),
+        &mut unknown,
+    );
+
+    for (key, value) in root {
+        let nested = match key.get_ref().as_ref() {
+            "profile" => fields_of::<ProfileToml>(),
+            "mappings" => fields_of::<PathMapping>(),
+            "servers" => {
+                if let DeValue::Table(servers) = value.get_ref() {
+                    for (name, server) in servers {
+                        if let DeValue::Table(server) = server.get_ref() {
+                            let prefix = format!("{section}servers.{}.", name.get_ref());
+                            let fields = fields_of::<ServerToml>();
+                            check_table(content, &prefix, server, fields, &mut unknown);
+                        }
+                    }
+                }
+                continue;
+            }
+            "kubernetes" => {
+                if let DeValue::Table(table) = value.get_ref() {
+                    let prefix = format!("{section}kubernetes.");
+                    let fields = fields_of::<Kubernetes>();
+                    check_table(content, &prefix, table, fields, &mut unknown);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        // The `{ append = [...] }` form of the lists holds the same tables
+        let tables = match value.get_ref() {
+            DeValue::Array(tables) => Some(tables),
+            DeValue::Table(list) => match list.get("append").map(|a| a.get_ref()) {
+                Some(DeValue::Array(tables)) => Some(tables),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(tables) = tables {
+            let prefix = format!("{section}{}.", key.get_ref());
+            for table in tables {
+                if let DeValue::Table(table) = table.get_ref() {
+                    check_table(content, &prefix, table, nested, &mut unknown);
+                }
+            }
+        }
+    }
+
+    unknown.sort_by_key(|u| (u.line, u.column));
+    unknown
+}
+
+fn check_table(
+    content: &str,
+    prefix: &str,
+    table: &DeTable,
+    fields: &'static [&'static str],
+    unknown: &mut Vec<UnknownKey>,
+) {
+    for key in table.keys() {
+        let name = key.get_ref().as_ref();
+        if fields.contains(&name) {
+            continue;
+        }
+
+        let (line, column) = line_column(content, key.span().start);
+        unknown.push(UnknownKey {
+            key: format!("{prefix}{name}"),
+            line,
+            column,
+            suggestion: suggest(name, fields),
+        });
+    }
+}
+
+/// The field with the closest name, if it is close enough to be a typo
+pub(super) fn suggest(name: &str, fields: &'static [&'static str]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|field| (levenshtein(name, field), *field))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+        .min()
+        .map(|(_, field)| field)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// 1-based line and column of a byte offset
+pub(super) fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Names of the fields of a struct that derives `Deserialize`, as seen by the deserializer
+pub(super) fn fields_of<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Introspect(&mut fields));
+    fields
+}
+
+/// A deserializer that only records the fields requested by `deserialize_struct`
+struct Introspect<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for Introspect<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        *self.0 = fields;
+        Err(Error::custom("fields recorded"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+const LAUNCHER_PLACEHOLDERS: &[&str] = &["container", "workdir", "executable"];
+
+/// Names of the `{name}` placeholders of a launcher argument
+fn placeholders(arg: &str) -> impl Iterator<Item = &str> {
+    arg.split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+/// Validate the values of the merged config
+pub(super) fn invalid_values(config: &ProxyConfigToml) -> Vec<String> {
+    let mut invalid = vec![];
+
+    if let Some(log_level) = &config.log_level
+        && let Err(e) = tracing_subscriber::EnvFilter::try_new(log_level)
+    {
+        invalid.push(format!("log_level `{log_level}` is not valid: {e}"));
+    }
+
+    let internal_paths = config.docker_internal_path.iter().chain(
+        config
+            .mappings
+            .iter()
+            .flat_map(MergeList::as_slice)
+            .map(|m| &m.docker_internal_path),
+    );
+    for path in internal_paths {
+        // The path is inside the container, that is always Unix
+        if !path.starts_with('/') {
+            invalid.push(format!("docker_internal_path `{path}` must be absolute"));
+        }
+    }
+
+    if let Some(launcher) = &config.launcher {
+        if launcher.is_empty() {
+            invalid.push("launcher must have at least the program".into());
+        }
+        for placeholder in launcher.iter().flat_map(|arg| placeholders(arg)) {
+            if !LAUNCHER_PLACEHOLDERS.contains(&placeholder) {
+                invalid.push(format!(
+                    "launcher placeholder `{{{placeholder}}}` is not valid, use one of {}",
+                    LAUNCHER_PLACEHOLDERS
+                        .iter()
+                        .map(|p| format!("`{{{p}}}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+    }
+
+    if let Some(local_path) = &config.local_path
+        && !Path::new(local_path).is_dir()
+    {
+        invalid.push(format!(
+            "local_path `{local_path}` is not an existing directory"
+        ));
+    }
+
+    invalid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys_with_suggestions() {
+        let content = r#"container = "app"
+docker_interal_path = "/app"
+
+[[profile]]
+pattern = "/home/me/dev"
+contaner = "api"
+
+[servers.pylsp]
+args = ["-m", "pylsp"]
+enviroment = { A = "b" }
+
+[variables]
+anything = "is allowed"
+"#;
+
+        let unknown = unknown_keys(content);
+        assert_eq!(
+            unknown,
+            vec![
+                UnknownKey {
+                    key: "docker_interal_path".into(),
+                    line: 2,
+                    column: 1,
+                    suggestion: Some("docker_internal_path"),
+                },
+                UnknownKey {
+                    key: "profile.contaner".into(),
+                    line: 6,
+                    column: 1,
+                    suggestion: Some("container"),
+                },
+                UnknownKey {
+                    key: "servers.pylsp.enviroment".into(),
+                    line: 10,
+                    column: 1,
+                    suggestion: None,
+                },
+            ]
+        );
+        assert_eq!(
+            unknown[0].to_string(),
+            "2:1: unknown key `docker_interal_path`, did you mean `docker_internal_path`?"
+        );
+
+        // The tables of the append form are checked too
+        let unknown =
+            unknown_keys(r#"mappings = { append = [{ local_path = "/a", docker_path = "/b" }] }"#);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].key, "mappings.docker_path");
+    }
+
+    #[test]
+    fn invalid_config_values() {
+        let config: ProxyConfigToml = toml::from_str(
+            r#"
+            log_level = "debug,tower=["
+            docker_internal_path = "app"
+            local_path = "/nonexistent/lspdock/project"
+            launcher = ["distrobox", "enter", "{box}", "--", "{executable}"]
+            "#,
+        )
+        .unwrap();
+
+        let invalid = invalid_values(&config);
+        assert_eq!(invalid.len(), 4);
+        assert!(invalid[1].contains("must be absolute"));
+        assert!(invalid[2].starts_with("launcher placeholder `{box}` is not valid"));
+
+        let config: ProxyConfigToml = toml::from_str(
+            r#"
+            log_level = "lspdock=debug,info"
+            docker_internal_path = "/app"
+            "#,
+        )
+        .unwrap();
+        assert!(invalid_values(&config).is_empty());
+    }
+}
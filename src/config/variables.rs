@@ -1,9 +1,90 @@
-use std::{env::current_dir, ffi::OsStr, ops::Deref};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    env::current_dir,
+    error::Error,
+    ffi::OsStr,
+    fmt::Display,
+    ops::Deref,
+    path::Path,
+    process::{Command, Stdio},
+};
 
-use super::ProxyConfigToml;
+use super::{MergeList, ProxyConfig, ProxyConfigToml, Runtime};
+use crate::container::{self, ContainerRuntime, inspect_container};
+
+pub(super) const DEFAULT_PROJECT_MARKERS: &[&str] = &[
+    "pyproject.toml",
+    "setup.py",
+    "package.json",
+    "Cargo.toml",
+    "go.mod",
+];
 
 pub trait VariableResolver {
-    fn expand(self, config: &mut ProxyConfigToml) -> Result<(), Box<dyn std::error::Error>>;
+    /// Name of the variable, used as `$NAME` or `${NAME}` in the config
+    fn name(&self) -> &str;
+
+    /// Value of the variable; it is called only when the variable is used in the config
+    fn resolve(&self, context: &ResolveContext) -> Result<String, Box<dyn Error>>;
+}
+
+/// Values of the config that the resolvers depend on
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveContext {
+    /// Container used to resolve the container-side variables
+    pub container: Option<String>,
+    /// User of `exec` in the container, e.g. for `$CONTAINER_HOME`
+    pub user: Option<String>,
+    /// Runtime of the container
+    pub runtime: Runtime,
+    /// Files that indicate the root of a project
+    pub project_markers: Vec<String>,
+}
+
+impl Default for ResolveContext {
+    fn default() -> Self {
+        Self {
+            container: None,
+            user: None,
+            runtime: Runtime::default(),
+            project_markers: DEFAULT_PROJECT_MARKERS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum VariableError {
+    /// `${NAME}` without a default and without a value
+    Undefined(String),
+    /// `${` without the closing brace
+    Unclosed(String),
+    InvalidName(String),
+    Resolver {
+        name: String,
+        error: String,
+    },
+    /// A user-defined variable that references itself, the chain of references is included
+    Cycle(Vec<String>),
+}
+
+impl Error for VariableError {}
+
+impl Display for VariableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Undefined(name) => write!(f, "variable {name} is not defined"),
+            Self::Unclosed(input) => write!(f, "missing closing brace in {input}"),
+            Self::InvalidName(name) => write!(f, "invalid variable name {name}"),
+            Self::Resolver { name, error } => {
+                write!(f, "variable {name} cannot be resolved: {error}")
+            }
+            Self::Cycle(chain) => write!(f, "cyclic variable reference {}", chain.join(" -> ")),
+        }
+    }
 }
 
 /// Base struct for variable resolvers that have a simple name field
@@ -25,109 +106,566 @@ impl Deref for Variable {
     }
 }
 
-/// Current working directory variable
-pub(super) struct VariableCwd(Variable);
-
-impl Default for VariableCwd {
-    fn default() -> Self {
-        Self(Variable::new("CWD"))
-    }
-}
+/// Declare a resolver with a simple name field
+macro_rules! variable {
+    ($(#[$doc:meta])* $ident:ident => $name:literal) => {
+        $(#[$doc])*
+        pub(super) struct $ident(Variable);
 
-/// Parent directory variable
-pub(super) struct VariableParent(Variable);
-
-impl Default for VariableParent {
-    fn default() -> Self {
-        Self(Variable::new("PARENT"))
-    }
+        impl Default for $ident {
+            fn default() -> Self {
+                Self(Variable::new($name))
+            }
+        }
+    };
 }
 
-/// Parent directory variable
-pub(super) struct VariableHome(Variable);
+variable!(
+    /// Current working directory variable
+    VariableCwd => "CWD"
+);
+variable!(
+    /// Parent directory variable
+    VariableParent => "PARENT"
+);
+variable!(
+    /// Home directory variable
+    VariableHome => "HOME"
+);
+variable!(
+    /// Top-level directory of the git worktree
+    VariableGitRoot => "GIT_ROOT"
+);
+variable!(
+    /// Nearest directory that contains one of the project markers
+    VariableProjectRoot => "PROJECT_ROOT"
+);
+variable!(
+    /// Current user name
+    VariableUser => "USER"
+);
+variable!(
+    /// Working directory of the container
+    VariableContainerWorkdir => "CONTAINER_WORKDIR"
+);
+variable!(
+    /// Home directory of the container user
+    VariableContainerHome => "CONTAINER_HOME"
+);
 
-impl Default for VariableHome {
-    fn default() -> Self {
-        Self(Variable::new("HOME"))
+impl VariableResolver for VariableCwd {
+    fn name(&self) -> &str {
+        &self.0
     }
-}
 
-impl VariableResolver for VariableCwd {
-    fn expand(self, config: &mut ProxyConfigToml) -> Result<(), Box<dyn std::error::Error>> {
-        let var = format!("${}", self.0.name.to_uppercase());
+    fn resolve(&self, _context: &ResolveContext) -> Result<String, Box<dyn Error>> {
         let cwd = current_dir()?;
         let expanded = cwd
             .to_str()
             .ok_or_else(|| "Could not convert current directory to string".to_string())?;
-        expand_into_config(config, &var, expanded);
 
-        Ok(())
+        Ok(expanded.to_string())
     }
 }
 
 impl VariableResolver for VariableParent {
-    fn expand(self, config: &mut ProxyConfigToml) -> Result<(), Box<dyn std::error::Error>> {
-        let var = format!("${}", self.0.name.to_uppercase());
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, _context: &ResolveContext) -> Result<String, Box<dyn Error>> {
         let cwd = current_dir()?;
         let parent = cwd.file_name().unwrap_or(OsStr::new(""));
         let expanded = parent
             .to_str()
             .ok_or_else(|| "Could not convert parent directory to string".to_string())?;
-        expand_into_config(config, &var, expanded);
 
-        Ok(())
+        Ok(expanded.to_string())
     }
 }
 
 impl VariableResolver for VariableHome {
-    fn expand(self, config: &mut ProxyConfigToml) -> Result<(), Box<dyn std::error::Error>> {
-        let var = format!("${}", self.0.name.to_uppercase());
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, _context: &ResolveContext) -> Result<String, Box<dyn Error>> {
         let home =
             dirs::home_dir().ok_or_else(|| "Could not retrieve home directory".to_string())?;
         let expanded = home
             .to_str()
             .ok_or_else(|| "Could not convert home to string".to_string())?;
-        expand_into_config(config, &var, expanded);
+
+        Ok(expanded.to_string())
+    }
+}
+
+impl VariableResolver for VariableGitRoot {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, _context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+        run_command("git", &["rev-parse", "--show-toplevel"])
+    }
+}
+
+impl VariableResolver for VariableProjectRoot {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+        let cwd = current_dir()?;
+        let root = find_project_root(&cwd, &context.project_markers).ok_or_else(|| {
+            format!(
+                "none of the project markers {:?} was found",
+                context.project_markers
+            )
+        })?;
+        let expanded = root
+            .to_str()
+            .ok_or_else(|| "Could not convert project root to string".to_string())?;
+
+        Ok(expanded.to_string())
+    }
+}
+
+impl VariableResolver for VariableUser {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, _context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .map_err(|_| "Could not retrieve the user name".to_string())?;
+
+        Ok(user)
+    }
+}
+
+impl VariableResolver for VariableContainerWorkdir {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+        let container = context
+            .container
+            .as_deref()
+            .ok_or_else(|| "container must be provided".to_string())?;
+        if !context.runtime.has_containers() {
+            return Err(format!(
+                "{} is not available with the {} runtime",
+                self.name(),
+                context.runtime
+            )
+            .into());
+        }
+        let runtime = container_runtime(context);
+        let inspect = block_on(inspect_container(&*runtime, container))?;
+        if inspect.config.working_dir.is_empty() {
+            return Err(format!("{container} has no working directory").into());
+        }
+        Ok(inspect.config.working_dir)
+    }
+}
+
+impl VariableResolver for VariableContainerHome {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn resolve(&self, context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+        let container = context
+            .container
+            .as_deref()
+            .ok_or_else(|| "container must be provided".to_string())?;
+        if !context.runtime.has_containers() {
+            return Err(format!(
+                "{} is not available with the {} runtime",
+                self.name(),
+                context.runtime
+            )
+            .into());
+        }
+        let config = ProxyConfig {
+            container: container.into(),
+            docker_internal_path: "/".into(),
+            user: context.user.clone(),
+            command: "sh".into(),
+            runtime: context.runtime,
+            ..Default::default()
+        };
+        let (program, args) =
+            container_runtime(context).exec(&config, &["-c".into(), "printf %s \"$HOME\"".into()]);
+        run_command(&program, &args)
+    }
+}
+
+/// The runtime of the container-side variables
+fn container_runtime(context: &ResolveContext) -> Box<dyn ContainerRuntime> {
+    container::runtime(&ProxyConfig {
+        runtime: context.runtime,
+        ..Default::default()
+    })
+}
+
+/// Wait for a future of the runtime. The variables are resolved while the config is read, which
+/// can be inside the async runtime of the proxy, then the future runs in its own thread.
+fn block_on<T: Send>(
+    future: impl Future<Output = std::io::Result<T>> + Send,
+) -> Result<T, Box<dyn Error>> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(future)
+            })
+            .join()
+            .map_err(|_| "the container runtime panicked".into())
+            .and_then(|result| Ok(result?))
+    })
+}
+
+/// Run a command and return its trimmed output
+fn run_command<S: AsRef<OsStr>>(cmd: &str, args: &[S]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{cmd} failed: {}", stderr.trim()).into());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if stdout.is_empty() {
+        return Err(format!("{cmd} returned an empty value").into());
+    }
+
+    Ok(stdout)
+}
+
+fn find_project_root<'a>(start: &'a Path, markers: &[String]) -> Option<&'a Path> {
+    start
+        .ancestors()
+        .find(|dir| markers.iter().any(|m| dir.join(m).exists()))
+}
+
+/// Expand the variables in the config values. The supported syntax is:
+///
+/// - `$NAME`: built-in variable, the name ends at the first character that is not alphanumeric
+///   or `_`; unknown names are kept as they are
+/// - `${NAME}`: built-in variable, or environment variable if there is no built-in one
+/// - `${env:NAME}`: environment variable
+/// - `${NAME:-default}`: use `default` if the variable is not defined or is empty
+/// - `$$`: a literal `$`
+///
+/// The user-defined variables of the `[variables]` table have precedence over the built-in ones,
+/// and can reference them and each other. Every variable is resolved lazily, only when it is used,
+/// and cached.
+pub(super) struct Interpolator {
+    resolvers: Vec<Box<dyn VariableResolver>>,
+    context: ResolveContext,
+    /// User-defined variables, not expanded
+    defined: HashMap<String, String>,
+    cache: RefCell<HashMap<String, String>>,
+    /// User-defined variables being resolved, used to detect cycles
+    resolving: RefCell<Vec<String>>,
+}
+
+impl Interpolator {
+    /// Interpolator with the built-in variables
+    pub(super) fn builtin() -> Self {
+        Self {
+            resolvers: vec![
+                Box::new(VariableCwd::default()),
+                Box::new(VariableParent::default()),
+                Box::new(VariableHome::default()),
+                Box::new(VariableGitRoot::default()),
+                Box::new(VariableProjectRoot::default()),
+                Box::new(VariableUser::default()),
+                Box::new(VariableContainerWorkdir::default()),
+                Box::new(VariableContainerHome::default()),
+            ],
+            context: ResolveContext::default(),
+            defined: HashMap::new(),
+            cache: RefCell::new(HashMap::new()),
+            resolving: RefCell::new(vec![]),
+        }
+    }
+
+    /// Define user variables, they override the previous definitions
+    fn define(&mut self, variables: &BTreeMap<String, String>) -> Result<(), VariableError> {
+        if variables.is_empty() {
+            return Ok(());
+        }
+
+        for (name, value) in variables {
+            validate_name(name)?;
+            self.defined.insert(name.clone(), value.clone());
+        }
+        self.cache.borrow_mut().clear();
 
         Ok(())
     }
+
+    /// Update the context, the cached values are discarded if it changes
+    fn set_context(&mut self, context: ResolveContext) {
+        if self.context != context {
+            self.context = context;
+            self.cache.borrow_mut().clear();
+        }
+    }
+
+    /// Expand every string field of the config. The container is expanded first because the
+    /// container-side variables depend on it; if the config doesn't provide it, the container
+    /// of the previous expansions is used.
+    pub(super) fn expand_config(
+        &mut self,
+        config: &mut ProxyConfigToml,
+    ) -> Result<(), VariableError> {
+        self.define(&config.variables)?;
+
+        let mut context = self.context.clone();
+
+        if let Some(markers) = config.project_markers.clone() {
+            let previous = MergeList::Replace(context.project_markers);
+            context.project_markers = MergeList::merge(Some(previous), Some(markers))
+                .map(MergeList::into_vec)
+                .unwrap_or_default();
+        }
+
+        if let Some(runtime) = config.runtime {
+            context.runtime = runtime;
+        }
+
+        let mut container = config.container.take();
+        if let Some(c) = container.as_mut() {
+            *c = self.interpolate(c)?;
+            context.container = Some(c.clone());
+        }
+        let mut user = config.user.take();
+        if let Some(u) = user.as_mut() {
+            *u = self.interpolate(u)?;
+            context.user = Some(u.clone());
+        }
+        self.set_context(context);
+
+        let result = self.expand_scopes(config);
+        config.container = container;
+        config.user = user;
+
+        result
+    }
+
+    /// Expand the top-level fields, then the profiles and the servers. The container-side
+    /// variables of a profile or a server refer to its own container when it sets one.
+    fn expand_scopes(&mut self, config: &mut ProxyConfigToml) -> Result<(), VariableError> {
+        self.expand_fields(config.strings_mut())?;
+
+        for profile in &mut config.profiles {
+            let mut container = profile.container.take();
+            let result = self.expand_scope(&mut container, profile.strings_mut());
+            profile.container = container;
+            result?;
+        }
+
+        for server in config.servers.values_mut() {
+            let mut container = server.container.take();
+            let result = self.expand_scope(&mut container, server.strings_mut());
+            server.container = container;
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Expand the container of a table, then its fields with that container in the context
+    fn expand_scope(
+        &mut self,
+        container: &mut Option<String>,
+        fields: Vec<&mut String>,
+    ) -> Result<(), VariableError> {
+        let Some(c) = container.as_mut() else {
+            return self.expand_fields(fields);
+        };
+        *c = self.interpolate(c)?;
+
+        let outer = self.context.clone();
+        self.set_context(ResolveContext {
+            container: Some(c.clone()),
+            ..outer.clone()
+        });
+        let result = self.expand_fields(fields);
+        self.set_context(outer);
+
+        result
+    }
+
+    fn expand_fields(&self, fields: Vec<&mut String>) -> Result<(), VariableError> {
+        fields
+            .into_iter()
+            .try_for_each(|field| self.interpolate(field).map(|v| *field = v))
+    }
+
+    pub(super) fn interpolate(&self, input: &str) -> Result<String, VariableError> {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(pos) = rest.find('$') {
+            output.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+
+            if let Some(after) = rest.strip_prefix('$') {
+                output.push('$');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('{') {
+                let end = closing_brace(after)
+                    .ok_or_else(|| VariableError::Unclosed(input.to_string()))?;
+                output.push_str(&self.expand_braced(&after[..end])?);
+                rest = &after[end + 1..];
+            } else {
+                let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+                let name = &rest[..len];
+                match self.lookup(name)? {
+                    Some(value) => output.push_str(&value),
+                    None => {
+                        output.push('$');
+                        output.push_str(name);
+                    }
+                }
+                rest = &rest[len..];
+            }
+        }
+        output.push_str(rest);
+
+        Ok(output)
+    }
+
+    /// Value of a variable, resolving it if it is not cached. Returns None if there is
+    /// no variable with that name
+    fn lookup(&self, name: &str) -> Result<Option<String>, VariableError> {
+        if let Some(value) = self.cache.borrow().get(name) {
+            return Ok(Some(value.clone()));
+        }
+
+        if let Some(template) = self.defined.get(name) {
+            if self.resolving.borrow().iter().any(|n| n == name) {
+                let mut chain = self.resolving.borrow().clone();
+                chain.push(name.to_string());
+                return Err(VariableError::Cycle(chain));
+            }
+
+            self.resolving.borrow_mut().push(name.to_string());
+            let value = self.interpolate(template);
+            self.resolving.borrow_mut().pop();
+
+            let value = value?;
+            self.cache
+                .borrow_mut()
+                .insert(name.to_string(), value.clone());
+            return Ok(Some(value));
+        }
+
+        let Some(resolver) = self.resolvers.iter().find(|r| r.name() == name) else {
+            return Ok(None);
+        };
+
+        let value = resolver
+            .resolve(&self.context)
+            .map_err(|e| VariableError::Resolver {
+                name: name.to_string(),
+                error: e.to_string(),
+            })?;
+        self.cache
+            .borrow_mut()
+            .insert(name.to_string(), value.clone());
+
+        Ok(Some(value))
+    }
+
+    /// Expand the content of `${...}`
+    fn expand_braced(&self, expr: &str) -> Result<String, VariableError> {
+        let (name, default) = match expr.find(":-") {
+            Some(i) => (&expr[..i], Some(&expr[i + 2..])),
+            None => (expr, None),
+        };
+
+        let value = match name.strip_prefix("env:") {
+            Some(env_name) => {
+                validate_name(env_name)?;
+                std::env::var(env_name).ok()
+            }
+            None => {
+                validate_name(name)?;
+                match self.lookup(name) {
+                    Ok(Some(value)) => Some(value),
+                    Ok(None) => std::env::var(name).ok(),
+                    // The default covers the variables that cannot be resolved
+                    Err(_) if default.is_some() => None,
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+
+        match (value, default) {
+            (Some(value), Some(default)) if value.is_empty() => self.interpolate(default),
+            (Some(value), _) => Ok(value),
+            (None, Some(default)) => self.interpolate(default),
+            (None, None) => Err(VariableError::Undefined(name.to_string())),
+        }
+    }
 }
 
-fn expand_into_config(config: &mut ProxyConfigToml, var: &str, expanded: &str) {
-    let fields = [
-        &mut config.container,
-        &mut config.docker_internal_path,
-        &mut config.executable,
-        &mut config.local_path,
-        &mut config.pattern,
-    ];
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
 
-    for field in fields {
-        if let Some(f) = field {
-            *field = Some(f.replace(var, expanded));
+fn validate_name(name: &str) -> Result<(), VariableError> {
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(VariableError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Position of the brace that closes an already opened `${`
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
         }
     }
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::provider::{ProfileToml, ServerToml};
+    use crate::test_support::temp_dir;
 
     #[test]
     fn variable_expand() {
-        let par_var = VariableParent::default();
-        let cwd_var = VariableCwd::default();
-        let home_var = VariableHome::default();
         let mut config = ProxyConfigToml {
             container: Some("$PARENT-web-1".into()),
             local_path: Some("$CWD/app".into()),
-            pattern: Some("$HOME/dev".into()),
+            pattern: Some(MergeList::Replace(vec!["$HOME/dev".into()])),
             ..Default::default()
         };
 
-        par_var.expand(&mut config).unwrap();
-        cwd_var.expand(&mut config).unwrap();
-        home_var.expand(&mut config).unwrap();
+        Interpolator::builtin().expand_config(&mut config).unwrap();
 
         let cwd = current_dir().unwrap();
         let parent = cwd.file_name().unwrap();
@@ -138,6 +676,221 @@ mod tests {
 
         assert_eq!(config.container, Some(format!("{parent}-web-1")));
         assert_eq!(config.local_path, Some(format!("{cwd}/app")));
-        assert_eq!(config.pattern.unwrap(), format!("{home}/dev"));
+        assert_eq!(config.pattern.unwrap().into_vec(), [format!("{home}/dev")]);
+    }
+
+    #[test]
+    fn interpolate_syntax() {
+        let interpolator = Interpolator::builtin();
+        interpolator
+            .cache
+            .borrow_mut()
+            .insert("HOME".into(), "/home/me".into());
+
+        // Token boundaries
+        assert_eq!(interpolator.interpolate("$HOMEDIR").unwrap(), "$HOMEDIR");
+        assert_eq!(
+            interpolator.interpolate("$HOME/dev").unwrap(),
+            "/home/me/dev"
+        );
+        assert_eq!(
+            interpolator.interpolate("${HOME}DIR").unwrap(),
+            "/home/meDIR"
+        );
+
+        // Escaping
+        assert_eq!(interpolator.interpolate("$$HOME").unwrap(), "$HOME");
+        assert_eq!(interpolator.interpolate("cost: 5$").unwrap(), "cost: 5$");
+
+        // Environment and defaults
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(interpolator.interpolate("${env:PATH}").unwrap(), path);
+        assert_eq!(
+            interpolator
+                .interpolate("${LSPDOCK_UNDEFINED_VAR:-app}-web-1")
+                .unwrap(),
+            "app-web-1"
+        );
+        assert_eq!(
+            interpolator
+                .interpolate("${env:LSPDOCK_UNDEFINED_VAR:-$HOME}")
+                .unwrap(),
+            "/home/me"
+        );
+
+        // Errors
+        assert!(matches!(
+            interpolator.interpolate("${LSPDOCK_UNDEFINED_VAR}"),
+            Err(VariableError::Undefined(_))
+        ));
+        assert!(matches!(
+            interpolator.interpolate("${HOME"),
+            Err(VariableError::Unclosed(_))
+        ));
+        assert!(matches!(
+            interpolator.interpolate("${HO ME}"),
+            Err(VariableError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn lazy_and_fallible_resolvers() {
+        let mut interpolator = Interpolator::builtin();
+
+        // Container variables without a container only fail when used
+        let mut config = ProxyConfigToml {
+            local_path: Some("$CWD".into()),
+            ..Default::default()
+        };
+        interpolator.expand_config(&mut config).unwrap();
+
+        let mut config = ProxyConfigToml {
+            docker_internal_path: Some("$CONTAINER_WORKDIR".into()),
+            ..Default::default()
+        };
+        match interpolator.expand_config(&mut config) {
+            Err(VariableError::Resolver { name, .. }) => assert_eq!(name, "CONTAINER_WORKDIR"),
+            other => panic!("unexpected result {other:?}"),
+        }
+
+        // The default covers the failure
+        let value = interpolator
+            .interpolate("${CONTAINER_WORKDIR:-/usr/src/app}")
+            .unwrap();
+        assert_eq!(value, "/usr/src/app");
+    }
+
+    /// Resolves to the container of the context
+    struct VariableContextContainer;
+
+    impl VariableResolver for VariableContextContainer {
+        fn name(&self) -> &str {
+            "CONTEXT_CONTAINER"
+        }
+
+        fn resolve(&self, context: &ResolveContext) -> Result<String, Box<dyn Error>> {
+            context
+                .container
+                .clone()
+                .ok_or_else(|| "no container".into())
+        }
+    }
+
+    #[test]
+    fn container_of_profiles_and_servers() {
+        let mut interpolator = Interpolator::builtin();
+        interpolator
+            .resolvers
+            .push(Box::new(VariableContextContainer));
+
+        let mut config = ProxyConfigToml {
+            container: Some("web-1".into()),
+            user: Some("vscode".into()),
+            docker_internal_path: Some("/$CONTEXT_CONTAINER".into()),
+            profiles: vec![
+                ProfileToml {
+                    container: Some("worker-1".into()),
+                    executable: Some("$CONTEXT_CONTAINER".into()),
+                    ..Default::default()
+                },
+                ProfileToml {
+                    executable: Some("$CONTEXT_CONTAINER".into()),
+                    ..Default::default()
+                },
+            ],
+            servers: [(
+                "pylsp".to_string(),
+                ServerToml {
+                    container: Some("tools-1".into()),
+                    args: Some(vec!["$CONTEXT_CONTAINER".into()]),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        interpolator.expand_config(&mut config).unwrap();
+
+        assert_eq!(config.docker_internal_path.as_deref(), Some("/web-1"));
+        assert_eq!(interpolator.context.user.as_deref(), Some("vscode"));
+        assert_eq!(config.profiles[0].executable.as_deref(), Some("worker-1"));
+        assert_eq!(config.profiles[1].executable.as_deref(), Some("web-1"));
+        assert_eq!(
+            config.servers["pylsp"].args,
+            Some(vec!["tools-1".to_string()])
+        );
+    }
+
+    #[test]
+    fn find_project_root_with_markers() {
+        let root = temp_dir("find_project_root");
+        let nested = root.join("src").join("module");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join("pyproject.toml"), "").unwrap();
+
+        let markers = vec!["pyproject.toml".to_string()];
+        assert_eq!(find_project_root(&nested, &markers), Some(root.as_path()));
+
+        let markers = vec!["lspdock-missing-marker".to_string()];
+        assert_eq!(find_project_root(&nested, &markers), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn user_defined_variables() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "${container_name}"
+            docker_internal_path = "$root/app"
+
+            [variables]
+            app = "$$PARENT-${project}"
+            project = "shop"
+            container_name = "${app}-web-1"
+            root = "/usr/src"
+            "#,
+        )
+        .unwrap();
+
+        Interpolator::builtin().expand_config(&mut config).unwrap();
+        assert_eq!(config.container.as_deref(), Some("$PARENT-shop-web-1"));
+        assert_eq!(config.docker_internal_path.as_deref(), Some("/usr/src/app"));
+
+        // A user variable that references a built-in one
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "${app}-web-1"
+
+            [variables]
+            app = "$PARENT"
+            "#,
+        )
+        .unwrap();
+
+        Interpolator::builtin().expand_config(&mut config).unwrap();
+        let cwd = current_dir().unwrap();
+        let parent = cwd.file_name().unwrap().to_str().unwrap();
+        assert_eq!(config.container, Some(format!("{parent}-web-1")));
+    }
+
+    #[test]
+    fn user_defined_variables_cycle() {
+        let mut config: ProxyConfigToml = toml::from_str(
+            r#"
+            container = "$a"
+
+            [variables]
+            a = "${b}-web"
+            b = "$c"
+            c = "$a"
+            "#,
+        )
+        .unwrap();
+
+        match Interpolator::builtin().expand_config(&mut config) {
+            Err(VariableError::Cycle(chain)) => assert_eq!(chain, ["a", "b", "c", "a"]),
+            other => panic!("unexpected result {other:?}"),
+        }
     }
 }
@@ -0,0 +1,122 @@
+use std::path::{Component, Path, PathBuf};
+
+use tracing::debug;
+
+use super::mounts::ContainerInspect;
+use crate::config::ComposeService;
+
+const SERVICE_LABEL: &str = "com.docker.compose.service";
+const PROJECT_LABEL: &str = "com.docker.compose.project";
+const WORKING_DIR_LABEL: &str = "com.docker.compose.project.working_dir";
+const CONFIG_FILES_LABEL: &str = "com.docker.compose.project.config_files";
+const CONTAINER_NUMBER_LABEL: &str = "com.docker.compose.container-number";
+
+/// Select the running container of the compose service. The project is the `project` name,
+/// the project that uses the compose `file`, or the project whose working directory contains
+/// the local path. The replica with the lowest container number is used when the service is
+/// scaled.
+pub fn select_compose_container(
+    compose: &ComposeService,
+    local_path: &str,
+    containers: &[ContainerInspect],
+) -> Option<String> {
+    let local = Path::new(local_path);
+    let file = compose.file.as_ref().map(|f| normalize(&local.join(f)));
+
+    let mut replicas: Vec<(u32, &str)> = containers
+        .iter()
+        .filter(|c| c.label(SERVICE_LABEL) == Some(compose.service.as_str()))
+        .filter(|c| match (&compose.project, &file) {
+            (Some(project), _) => c.label(PROJECT_LABEL) == Some(project.as_str()),
+            (None, Some(file)) => c
+                .label(CONFIG_FILES_LABEL)
+                .is_some_and(|files| files.split(',').any(|f| Path::new(f) == file)),
+            (None, None) => c
+                .label(WORKING_DIR_LABEL)
+                .is_some_and(|wd| local.starts_with(wd)),
+        })
+        .map(|c| {
+            let number = c
+                .label(CONTAINER_NUMBER_LABEL)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(u32::MAX);
+            (number, c.name())
+        })
+        .collect();
+
+    replicas.sort();
+    debug!(service=%compose.service, ?replicas, "Compose service replicas");
+
+    replicas.first().map(|(_, name)| name.to_string())
+}
+
+/// Resolve the `.` and `..` components without accessing the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::fixtures::containers;
+
+    #[test]
+    fn select_compose_service_replica() {
+        let containers = containers();
+        let service = |service: &str| ComposeService {
+            service: service.into(),
+            ..Default::default()
+        };
+
+        // The project is the one that contains the local path, the lowest replica is used
+        assert_eq!(
+            select_compose_container(&service("web"), "/home/me/dev/shop/src", &containers)
+                .as_deref(),
+            Some("shop-web-2")
+        );
+        assert_eq!(
+            select_compose_container(&service("web"), "/home/me/dev/api", &containers).as_deref(),
+            Some("custom-name")
+        );
+        assert_eq!(
+            select_compose_container(&service("web"), "/home/me/work", &containers),
+            None
+        );
+
+        let by_project = ComposeService {
+            project: Some("api".into()),
+            ..service("web")
+        };
+        assert_eq!(
+            select_compose_container(&by_project, "/home/me/work", &containers).as_deref(),
+            Some("custom-name")
+        );
+
+        let by_file = ComposeService {
+            file: Some("../shop/./compose.yaml".into()),
+            ..service("db")
+        };
+        assert_eq!(
+            select_compose_container(&by_file, "/home/me/dev/api", &containers).as_deref(),
+            Some("shop-db-1")
+        );
+        let by_file = ComposeService {
+            file: Some("compose.yaml".into()),
+            ..service("db")
+        };
+        assert_eq!(
+            select_compose_container(&by_file, "/home/me/dev/api", &containers),
+            None
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use tracing::{debug, warn};
+
+use super::mounts::ContainerInspect;
+use super::runtime::ContainerRuntime;
+
+const COMPOSE_WORKING_DIR_LABEL: &str = "com.docker.compose.project.working_dir";
+const DEVCONTAINER_FOLDER_LABEL: &str = "devcontainer.local_folder";
+
+/// Inspect every running container
+pub async fn list_running_containers(
+    runtime: &dyn ContainerRuntime,
+) -> std::io::Result<Vec<ContainerInspect>> {
+    let ids = runtime.running().await?;
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    runtime.inspect(&ids).await
+}
+
+/// Select the container whose bind mounts contain the local path. The containers that belong
+/// to the compose project of the local path are preferred, then the most specific mount.
+pub fn select_container(local_path: &str, containers: &[ContainerInspect]) -> Option<String> {
+    let local = Path::new(local_path);
+
+    let mut candidates: Vec<((bool, usize), &str)> = containers
+        .iter()
+        .filter_map(|c| {
+            let mount_len = c
+                .mounts
+                .iter()
+                .filter(|m| m.r#type == "bind" && !m.source.is_empty())
+                .filter(|m| local.starts_with(&m.source))
+                .map(|m| m.source.len())
+                .max()?;
+            let same_project = c
+                .label(COMPOSE_WORKING_DIR_LABEL)
+                .is_some_and(|wd| local.starts_with(wd));
+            Some(((same_project, mount_len), c.name()))
+        })
+        .collect();
+
+    // Best rank first, then by name to be deterministic
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+    let (rank, name) = *candidates.first()?;
+    let tied: Vec<&str> = candidates
+        .iter()
+        .filter(|(r, _)| *r == rank)
+        .map(|(_, n)| *n)
+        .collect();
+
+    if tied.len() > 1 {
+        warn!(candidates=?tied, selected=%name, "Several containers mount the project directory");
+    } else {
+        debug!(container=%name, "Container discovered");
+    }
+
+    Some(name.to_string())
+}
+
+/// Select the container created for the dev container of `local_folder`, the first by name if
+/// there are several
+pub fn select_devcontainer(local_folder: &str, containers: &[ContainerInspect]) -> Option<String> {
+    containers
+        .iter()
+        .filter(|c| c.label(DEVCONTAINER_FOLDER_LABEL) == Some(local_folder))
+        .map(ContainerInspect::name)
+        .min()
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::fixtures::containers;
+
+    #[test]
+    fn select_container_of_the_same_project() {
+        let containers = containers();
+
+        // Both api containers are tied, the first by name is selected
+        assert_eq!(
+            select_container("/home/me/dev/api/src", &containers).as_deref(),
+            Some("api-web-1")
+        );
+        assert_eq!(
+            select_container("/home/me/dev/shop", &containers).as_deref(),
+            Some("shop-web-1")
+        );
+        assert_eq!(select_container("/home/me/work", &containers), None);
+    }
+
+    #[test]
+    fn select_devcontainer_by_label() {
+        let containers = containers();
+        assert_eq!(
+            select_devcontainer("/home/me/dev/shop", &containers).as_deref(),
+            Some("vsc-shop-5f2c")
+        );
+        assert_eq!(select_devcontainer("/home/me/dev", &containers), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! Running containers shared by the unit tests of the container resolution
+
+use super::mounts::ContainerInspect;
+
+/// The `docker inspect` of the running containers: plain containers that mount the projects,
+/// the replicas of compose services, a dev container and containers with images and labels
+pub fn containers() -> Vec<ContainerInspect> {
+    serde_json::from_str(
+        r#"[
+            {
+                "Name": "/shop-web-1",
+                "Mounts": [{"Type": "bind", "Source": "/home/me/dev", "Destination": "/dev"}],
+                "Config": {"Labels": null}
+            },
+            {
+                "Name": "/api-worker-1",
+                "Mounts": [{"Type": "bind", "Source": "/home/me/dev/api", "Destination": "/app"}],
+                "Config": {"Labels": {"com.docker.compose.project.working_dir": "/home/me/dev/api"}}
+            },
+            {
+                "Name": "/api-web-1",
+                "Mounts": [{"Type": "bind", "Source": "/home/me/dev/api", "Destination": "/app"}],
+                "Config": {"Labels": {"com.docker.compose.project.working_dir": "/home/me/dev/api"}}
+            },
+            {
+                "Name": "/vsc-shop-5f2c",
+                "Config": {"Labels": {"devcontainer.local_folder": "/home/me/dev/shop"}}
+            },
+            {
+                "Name": "/other-web-1",
+                "Mounts": [{"Type": "bind", "Source": "/home/me/other", "Destination": "/app"}]
+            },
+            {
+                "Name": "/shop-web-3",
+                "Config": {"Labels": {
+                    "com.docker.compose.project": "shop",
+                    "com.docker.compose.service": "web",
+                    "com.docker.compose.container-number": "3",
+                    "com.docker.compose.project.working_dir": "/home/me/dev/shop",
+                    "com.docker.compose.project.config_files": "/home/me/dev/shop/compose.yaml"
+                }}
+            },
+            {
+                "Name": "/shop-web-2",
+                "Config": {"Labels": {
+                    "com.docker.compose.project": "shop",
+                    "com.docker.compose.service": "web",
+                    "com.docker.compose.container-number": "2",
+                    "com.docker.compose.project.working_dir": "/home/me/dev/shop",
+                    "com.docker.compose.project.config_files": "/home/me/dev/shop/compose.yaml"
+                }}
+            },
+            {
+                "Name": "/shop-db-1",
+                "Config": {"Labels": {
+                    "com.docker.compose.project": "shop",
+                    "com.docker.compose.service": "db",
+                    "com.docker.compose.container-number": "1",
+                    "com.docker.compose.project.working_dir": "/home/me/dev/shop",
+                    "com.docker.compose.project.config_files": "/home/me/dev/shop/compose.yaml"
+                }}
+            },
+            {
+                "Name": "/custom-name",
+                "Config": {"Labels": {
+                    "com.docker.compose.project": "api",
+                    "com.docker.compose.service": "web",
+                    "com.docker.compose.container-number": "1",
+                    "com.docker.compose.project.working_dir": "/home/me/dev/api",
+                    "com.docker.compose.project.config_files": "/home/me/dev/api/compose.yaml"
+                }}
+            },
+            {
+                "Id": "4f2a9c1e",
+                "Name": "/ci-python-7",
+                "Config": {"Image": "registry.local/team/python:3.12", "Labels": {"dev.team/lsp": "python"}}
+            },
+            {
+                "Id": "9b7e0d3a",
+                "Name": "/ci-node-2",
+                "Config": {"Image": "registry.local/team/node", "Labels": {"dev.team/lsp": "node"}}
+            },
+            {
+                "Id": "9b1c5f7d",
+                "Name": "/ci-node-3",
+                "Config": {"Image": "registry.local/team/node@sha256:abc", "Labels": {"dev.team/lsp": "node"}}
+            }
+        ]"#,
+    )
+    .unwrap()
+}
@@ -0,0 +1,14 @@
+mod compose;
+mod discover;
+#[cfg(test)]
+mod fixtures;
+mod mounts;
+mod runtime;
+mod selector;
+mod startup;
+
+pub use compose::select_compose_container;
+pub use discover::{list_running_containers, select_container, select_devcontainer};
+pub use mounts::{derive_mappings, inspect_container};
+pub use runtime::{ContainerRuntime, ContainerStatus, runtime};
+pub use startup::prepare;
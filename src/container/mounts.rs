@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::{debug, trace};
+
+use super::runtime::ContainerRuntime;
+use crate::config::{PathMapping, ProxyConfig};
+
+/// The subset of `docker inspect` used to derive the path mappings
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInspect {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    #[serde(default)]
+    pub config: InspectConfig,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct InspectConfig {
+    #[serde(default)]
+    pub working_dir: String,
+    #[serde(default)]
+    pub image: String,
+    #[serde(default)]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+impl ContainerInspect {
+    /// Name of the container without the leading slash
+    pub fn name(&self) -> &str {
+        self.name.trim_start_matches('/')
+    }
+
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.config.labels.as_ref()?.get(key).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct Mount {
+    #[serde(default)]
+    pub r#type: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub destination: String,
+}
+
+/// Retrieve the mount table and the working directory of the container
+pub async fn inspect_container(
+    runtime: &dyn ContainerRuntime,
+    container: &str,
+) -> std::io::Result<ContainerInspect> {
+    let inspect = runtime.inspect(&[container]).await?;
+    trace!(?inspect);
+
+    inspect
+        .into_iter()
+        .next()
+        .ok_or_else(|| std::io::Error::other(format!("container {container} not found")))
+}
+
+/// Build the host ↔ container mappings from the bind mounts that cover the local path. The root
+/// is the container path of the mount that contains the local path, or the container working
+/// directory if none of them contains it. Returns false if the root cannot be derived.
+pub fn derive_mappings(config: &mut ProxyConfig, inspect: &ContainerInspect) -> bool {
+    let local = Path::new(&config.local_path);
+
+    let binds: Vec<&Mount> = inspect
+        .mounts
+        .iter()
+        .filter(|m| m.r#type == "bind" && !m.source.is_empty() && !m.destination.is_empty())
+        .collect();
+
+    // The mounts that contain the local path, the longest one is the root
+    let covering: Vec<&Mount> = binds
+        .iter()
+        .copied()
+        .filter(|m| local.starts_with(&m.source))
+        .collect();
+
+    let root = covering.iter().max_by_key(|m| m.source.len()).map(|m| {
+        let rest = local.strip_prefix(&m.source).unwrap_or(Path::new(""));
+        Path::new(&m.destination)
+            .join(rest)
+            .to_string_lossy()
+            .to_string()
+    });
+
+    // Keep the mounts related to the project: the ones that contain the local path and the
+    // ones nested inside them or inside the local path
+    for mount in binds {
+        let source = Path::new(&mount.source);
+        let related = source.starts_with(local)
+            || covering
+                .iter()
+                .any(|c| source.starts_with(Path::new(&c.source)));
+        if !related {
+            continue;
+        }
+
+        let mapping = PathMapping {
+            local_path: mount.source.clone(),
+            docker_internal_path: mount.destination.clone(),
+        };
+        if !config.mappings.contains(&mapping) {
+            debug!(?mapping, "Mapping derived from the container mounts");
+            config.mappings.push(mapping);
+        }
+    }
+
+    match root {
+        Some(docker_path) => config.docker_internal_path = docker_path,
+        None if !inspect.config.working_dir.is_empty() => {
+            config.docker_internal_path = inspect.config.working_dir.clone();
+        }
+        None => return false,
+    }
+
+    debug!(docker_internal_path=%config.docker_internal_path, "Root derived from the container");
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn construct_inspect() -> ContainerInspect {
+        serde_json::from_str(
+            r#"{
+                "Mounts": [
+                    {"Type": "bind", "Source": "/home/me/dev/api", "Destination": "/usr/src/app"},
+                    {"Type": "bind", "Source": "/home/me/dev/api/.venv", "Destination": "/venv"},
+                    {"Type": "bind", "Source": "/home/me/other", "Destination": "/other"},
+                    {"Type": "volume", "Source": "/var/lib/docker/volumes/x", "Destination": "/data"}
+                ],
+                "Config": {"WorkingDir": "/workdir"}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn derive_mappings_from_bind_mounts() {
+        let inspect = construct_inspect();
+        let mut config = ProxyConfig {
+            local_path: "/home/me/dev/api/src".into(),
+            ..Default::default()
+        };
+
+        assert!(derive_mappings(&mut config, &inspect));
+        assert_eq!(config.docker_internal_path, "/usr/src/app/src");
+        assert_eq!(
+            config.mappings,
+            vec![
+                PathMapping {
+                    local_path: "/home/me/dev/api".into(),
+                    docker_internal_path: "/usr/src/app".into(),
+                },
+                PathMapping {
+                    local_path: "/home/me/dev/api/.venv".into(),
+                    docker_internal_path: "/venv".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn derive_root_from_working_dir() {
+        let inspect = construct_inspect();
+        let mut config = ProxyConfig {
+            local_path: "/home/me/elsewhere".into(),
+            ..Default::default()
+        };
+
+        assert!(derive_mappings(&mut config, &inspect));
+        assert_eq!(config.docker_internal_path, "/workdir");
+        assert!(config.mappings.is_empty());
+
+        let inspect = ContainerInspect::default();
+        assert!(!derive_mappings(&mut config, &inspect));
+    }
+}
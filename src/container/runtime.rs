@@ -0,0 +1,836 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+
+use tokio::process::Command;
+use tracing::trace;
+
+use super::mounts::ContainerInspect;
+use crate::config::{Kubernetes, ProxyConfig, Runtime, shell_join};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// State of a container
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContainerStatus {
+    Running,
+    Stopped,
+    NotFound,
+}
+
+/// A program that runs the LSP inside a container and reads the state of the containers
+pub trait ContainerRuntime: Send + Sync {
+    /// Whether the container exists and is running
+    fn status<'a>(&'a self, container: &'a str) -> BoxFuture<'a, std::io::Result<ContainerStatus>>;
+
+    /// Program and arguments that spawn the `command` of the config with the arguments `args`
+    /// inside the container, with the stdin attached
+    fn exec(&self, config: &ProxyConfig, args: &[String]) -> (String, Vec<String>);
+
+    /// Whether the `env` of the config is set in the spawned process, because the command of
+    /// `exec` cannot pass it
+    fn process_env(&self) -> bool {
+        false
+    }
+
+    /// Content of a file inside the container
+    fn read_file<'a>(
+        &'a self,
+        container: &'a str,
+        path: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<Vec<u8>>>;
+
+    /// Mounts, labels and working directory of the containers
+    fn inspect<'a>(
+        &'a self,
+        containers: &'a [&'a str],
+    ) -> BoxFuture<'a, std::io::Result<Vec<ContainerInspect>>>;
+
+    /// IDs of the running containers
+    fn running(&self) -> BoxFuture<'_, std::io::Result<Vec<String>>>;
+
+    /// Container selected by the runtime itself when the config does not name it, e.g. the pod
+    /// of a label selector. None when the runtime has no selection of its own.
+    fn select(&self) -> BoxFuture<'_, Option<Result<String, String>>> {
+        Box::pin(async { None })
+    }
+}
+
+/// The runtime selected by the config, the launcher has precedence over `runtime`
+pub fn runtime(config: &ProxyConfig) -> Box<dyn ContainerRuntime> {
+    if let Some(template) = &config.launcher {
+        return Box::new(Launcher {
+            template: template.clone(),
+            workdir: config.docker_internal_path.clone(),
+        });
+    }
+
+    match config.runtime.resolve().0 {
+        Runtime::Podman => Box::new(PODMAN),
+        Runtime::Kubernetes => Box::new(KubernetesCli {
+            program: "kubectl".into(),
+            kubernetes: config.kubernetes.clone(),
+        }),
+        Runtime::Ssh => Box::new(SshCli {
+            program: "ssh".into(),
+            user: config.user.clone(),
+        }),
+        _ => Box::new(DOCKER),
+    }
+}
+
+/// A Docker-compatible CLI, the commands and their output are the same except for the
+/// differences listed in its fields
+pub struct ContainerCli {
+    program: &'static str,
+    /// Subcommand that inspects only containers; `podman inspect` also matches pods and volumes
+    inspect: &'static [&'static str],
+}
+
+pub const DOCKER: ContainerCli = ContainerCli {
+    program: "docker",
+    inspect: &["inspect"],
+};
+
+pub const PODMAN: ContainerCli = ContainerCli {
+    program: "podman",
+    inspect: &["container", "inspect"],
+};
+
+impl ContainerCli {
+    async fn run(&self, args: &[&str]) -> std::io::Result<Vec<u8>> {
+        trace!(program = self.program, ?args, "Running");
+        let output = Command::new(self.program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(std::io::Error::other(format!(
+                "{} {} failed: {}",
+                self.program,
+                args.first().unwrap_or(&""),
+                stderr.trim()
+            )));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+impl ContainerRuntime for ContainerCli {
+    fn status<'a>(&'a self, container: &'a str) -> BoxFuture<'a, std::io::Result<ContainerStatus>> {
+        Box::pin(async move {
+            let output = Command::new(self.program)
+                .args(self.inspect)
+                .args(["-f", "{{.State.Running}}", container])
+                .stdin(Stdio::null())
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                return Ok(ContainerStatus::NotFound);
+            }
+            if String::from_utf8_lossy(&output.stdout).trim() == "true" {
+                Ok(ContainerStatus::Running)
+            } else {
+                Ok(ContainerStatus::Stopped)
+            }
+        })
+    }
+
+    fn exec(&self, config: &ProxyConfig, server_args: &[String]) -> (String, Vec<String>) {
+        let mut args: Vec<String> = vec![
+            "exec".into(),
+            "-i".into(),
+            "--workdir".into(),
+            config.docker_internal_path.clone(),
+        ];
+        if let Some(user) = &config.user {
+            args.extend(["--user".into(), user.clone()]);
+        }
+        for (key, value) in &config.env {
+            args.extend(["-e".into(), format!("{key}={value}")]);
+        }
+        args.extend([config.container.clone(), config.command.clone()]);
+        args.extend_from_slice(server_args);
+        (self.program.into(), args)
+    }
+
+    fn read_file<'a>(
+        &'a self,
+        container: &'a str,
+        path: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<Vec<u8>>> {
+        Box::pin(async move { self.run(&["exec", container, "cat", path]).await })
+    }
+
+    fn inspect<'a>(
+        &'a self,
+        containers: &'a [&'a str],
+    ) -> BoxFuture<'a, std::io::Result<Vec<ContainerInspect>>> {
+        Box::pin(async move {
+            let args = [self.inspect, containers].concat();
+            let output = self.run(&args).await?;
+            let inspect: Vec<ContainerInspect> = serde_json::from_slice(&output)?;
+            Ok(inspect)
+        })
+    }
+
+    fn running(&self) -> BoxFuture<'_, std::io::Result<Vec<String>>> {
+        Box::pin(async move {
+            let output = self.run(&["ps", "-q"]).await?;
+            Ok(String::from_utf8_lossy(&output)
+                .split_whitespace()
+                .map(String::from)
+                .collect())
+        })
+    }
+}
+
+/// A command template that runs a program in an environment, e.g. `distrobox enter`,
+/// `toolbox run`, `nix develop -c` or `direnv exec`. The environments cannot be listed nor
+/// inspected; they are expected to be available.
+pub struct Launcher {
+    template: Vec<String>,
+    workdir: String,
+}
+
+impl Launcher {
+    /// Replace the placeholders of the template; the executable is appended when the template
+    /// does not have `{executable}`
+    fn command(&self, container: &str, executable: &str) -> (String, Vec<String>) {
+        let mut has_executable = false;
+        let mut args: Vec<String> = self
+            .template
+            .iter()
+            .map(|arg| {
+                has_executable |= arg.contains("{executable}");
+                arg.replace("{container}", container)
+                    .replace("{workdir}", &self.workdir)
+                    .replace("{executable}", executable)
+            })
+            .collect();
+        if !has_executable {
+            args.push(executable.into());
+        }
+
+        let program = if args.is_empty() {
+            String::new()
+        } else {
+            args.remove(0)
+        };
+        (program, args)
+    }
+
+    fn unsupported(&self, action: &str) -> std::io::Error {
+        let program = self
+            .template
+            .first()
+            .map(String::as_str)
+            .unwrap_or_default();
+        std::io::Error::other(format!("the launcher {program} cannot {action}"))
+    }
+}
+
+impl ContainerRuntime for Launcher {
+    fn status<'a>(
+        &'a self,
+        _container: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<ContainerStatus>> {
+        Box::pin(async { Ok(ContainerStatus::Running) })
+    }
+
+    fn exec(&self, config: &ProxyConfig, server_args: &[String]) -> (String, Vec<String>) {
+        let (program, mut args) = self.command(&config.container, &config.command);
+        args.extend_from_slice(server_args);
+        (program, args)
+    }
+
+    fn process_env(&self) -> bool {
+        true
+    }
+
+    fn read_file<'a>(
+        &'a self,
+        container: &'a str,
+        path: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<Vec<u8>>> {
+        Box::pin(async move {
+            let (program, mut args) = self.command(container, "cat");
+            args.push(path.into());
+
+            let output = Command::new(&program)
+                .args(&args)
+                .stdout(Stdio::piped())
+                .stdin(Stdio::null())
+                .stderr(Stdio::piped())
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(std::io::Error::other(format!(
+                    "{program} failed: {}",
+                    stderr.trim()
+                )));
+            }
+
+            Ok(output.stdout)
+        })
+    }
+
+    fn inspect<'a>(
+        &'a self,
+        _containers: &'a [&'a str],
+    ) -> BoxFuture<'a, std::io::Result<Vec<ContainerInspect>>> {
+        Box::pin(async move { Err(self.unsupported("inspect the mounts")) })
+    }
+
+    fn running(&self) -> BoxFuture<'_, std::io::Result<Vec<String>>> {
+        Box::pin(async move { Err(self.unsupported("list the environments")) })
+    }
+}
+
+/// `kubectl` with the pods of the `[kubernetes]` table, the pods are the containers of the
+/// other runtimes. The source is expected to be synced to the pod, it has no mounts to inspect.
+pub struct KubernetesCli {
+    program: String,
+    kubernetes: Kubernetes,
+}
+
+impl KubernetesCli {
+    /// `kubectl` arguments of a subcommand in the namespace of the config
+    fn args(&self, subcommand: &[&str]) -> Vec<String> {
+        let mut args: Vec<String> = subcommand.iter().map(|arg| arg.to_string()).collect();
+        if let Some(namespace) = &self.kubernetes.namespace {
+            args.extend(["-n".into(), namespace.clone()]);
+        }
+        args
+    }
+
+    /// `kubectl exec` arguments up to the `--` that precedes the command
+    fn exec_args(&self, pod: &str, stdin: bool) -> Vec<String> {
+        let mut args = self.args(if stdin { &["exec", "-i"] } else { &["exec"] });
+        args.push(pod.into());
+        if let Some(container) = &self.kubernetes.container {
+            args.extend(["-c".into(), container.clone()]);
+        }
+        args.push("--".into());
+        args
+    }
+
+    async fn run(&self, args: &[String]) -> std::io::Result<Vec<u8>> {
+        trace!(program = self.program, ?args, "Running");
+        let output = Command::new(&self.program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(std::io::Error::other(format!(
+                "{} {} failed: {}",
+                self.program,
+                args.first().map(String::as_str).unwrap_or_default(),
+                // The last line is the message, the previous ones are the client logs
+                stderr.trim().lines().last().unwrap_or_default()
+            )));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+impl ContainerRuntime for KubernetesCli {
+    fn status<'a>(&'a self, pod: &'a str) -> BoxFuture<'a, std::io::Result<ContainerStatus>> {
+        Box::pin(async move {
+            let mut args = self.args(&["get", "pod"]);
+            args.extend([pod.into(), "-o".into(), "jsonpath={.status.phase}".into()]);
+            match self.run(&args).await {
+                Ok(phase) if String::from_utf8_lossy(&phase).trim() == "Running" => {
+                    Ok(ContainerStatus::Running)
+                }
+                Ok(_) => Ok(ContainerStatus::Stopped),
+                Err(_) => Ok(ContainerStatus::NotFound),
+            }
+        })
+    }
+
+    /// `kubectl exec` has no working directory nor environment options, a shell sets them. The
+    /// user of the pod cannot be changed.
+    fn exec(&self, config: &ProxyConfig, server_args: &[String]) -> (String, Vec<String>) {
+        let mut args = self.exec_args(&config.container, true);
+        args.extend([
+            "sh".into(),
+            "-c".into(),
+            r#"cd "$0" && exec "$@""#.into(),
+            config.docker_internal_path.clone(),
+        ]);
+        if !config.env.is_empty() {
+            args.push("env".into());
+            args.extend(
+                config
+                    .env
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}")),
+            );
+        }
+        args.push(config.command.clone());
+        args.extend_from_slice(server_args);
+        (self.program.clone(), args)
+    }
+
+    fn read_file<'a>(
+        &'a self,
+        pod: &'a str,
+        path: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<Vec<u8>>> {
+        Box::pin(async move {
+            let mut args = self.exec_args(pod, false);
+            args.extend(["cat".into(), path.into()]);
+            self.run(&args).await
+        })
+    }
+
+    fn inspect<'a>(
+        &'a self,
+        _pods: &'a [&'a str],
+    ) -> BoxFuture<'a, std::io::Result<Vec<ContainerInspect>>> {
+        Box::pin(async {
+            Err(std::io::Error::other(
+                "the mounts of a pod cannot be inspected, set docker_internal_path",
+            ))
+        })
+    }
+
+    fn running(&self) -> BoxFuture<'_, std::io::Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut args = self.args(&["get", "pods"]);
+            if let Some(selector) = &self.kubernetes.selector {
+                args.extend(["-l".into(), selector.clone()]);
+            }
+            args.extend([
+                "--field-selector=status.phase=Running".into(),
+                "-o".into(),
+                "jsonpath={.items[*].metadata.name}".into(),
+            ]);
+            let output = self.run(&args).await?;
+            let mut pods: Vec<String> = String::from_utf8_lossy(&output)
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+            pods.sort();
+            Ok(pods)
+        })
+    }
+
+    fn select(&self) -> BoxFuture<'_, Option<Result<String, String>>> {
+        Box::pin(async move {
+            let selector = self.kubernetes.selector.as_ref()?;
+            Some(match self.running().await {
+                Ok(pods) => match pods.as_slice() {
+                    [] => Err(format!("no running pod matches {selector}")),
+                    [pod] => Ok(pod.clone()),
+                    pods => Err(format!(
+                        "{selector} matches {} running pods: {}; use a more specific selector",
+                        pods.len(),
+                        pods.join(", ")
+                    )),
+                },
+                Err(e) => Err(format!("failed to list the pods: {e}")),
+            })
+        })
+    }
+}
+
+/// `ssh` to a remote host that has the project at `docker_internal_path`, e.g. synced with
+/// rsync or mounted with NFS. Every command is a new `ssh` with the same host and options, then
+/// a `ControlMaster` of the SSH config shares the connection of the LSP.
+pub struct SshCli {
+    program: String,
+    user: Option<String>,
+}
+
+impl SshCli {
+    /// `ssh` arguments that run a shell command in the host. ssh joins the arguments after the
+    /// host into the command of the remote shell, then the values are quoted.
+    fn args(&self, host: &str, command: String) -> Vec<String> {
+        let mut args = vec!["-T".into()];
+        if let Some(user) = &self.user {
+            args.extend(["-l".into(), user.clone()]);
+        }
+        args.extend([host.into(), "--".into(), command]);
+        args
+    }
+
+    async fn run(&self, host: &str, command: String) -> std::io::Result<Vec<u8>> {
+        let args = self.args(host, command);
+        trace!(program = self.program, ?args, "Running");
+        let output = Command::new(&self.program)
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(std::io::Error::other(format!(
+                "{} {host} failed: {}",
+                self.program,
+                stderr.trim()
+            )));
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn unsupported(&self, action: &str) -> std::io::Error {
+        std::io::Error::other(format!("{} cannot {action}", self.program))
+    }
+}
+
+impl ContainerRuntime for SshCli {
+    fn status<'a>(&'a self, host: &'a str) -> BoxFuture<'a, std::io::Result<ContainerStatus>> {
+        Box::pin(async move {
+            // The error of ssh tells why the host cannot be reached
+            self.run(host, "true".into()).await?;
+            Ok(ContainerStatus::Running)
+        })
+    }
+
+    /// The user of `exec` is the `-l` of ssh
+    fn exec(&self, config: &ProxyConfig, server_args: &[String]) -> (String, Vec<String>) {
+        let mut command = vec!["exec".to_string()];
+        if !config.env.is_empty() {
+            command.push("env".into());
+            command.extend(
+                config
+                    .env
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}")),
+            );
+        }
+        command.push(config.command.clone());
+        command.extend_from_slice(server_args);
+        let command = format!(
+            "cd {} && {}",
+            shell_join(std::slice::from_ref(&config.docker_internal_path)),
+            shell_join(&command)
+        );
+        (self.program.clone(), self.args(&config.container, command))
+    }
+
+    fn read_file<'a>(
+        &'a self,
+        host: &'a str,
+        path: &'a str,
+    ) -> BoxFuture<'a, std::io::Result<Vec<u8>>> {
+        Box::pin(async move {
+            let command = format!("cat {}", shell_join(&[path.to_string()]));
+            self.run(host, command).await
+        })
+    }
+
+    fn inspect<'a>(
+        &'a self,
+        _hosts: &'a [&'a str],
+    ) -> BoxFuture<'a, std::io::Result<Vec<ContainerInspect>>> {
+        Box::pin(async move {
+            Err(self.unsupported("inspect the mounts of a host, set docker_internal_path"))
+        })
+    }
+
+    fn running(&self) -> BoxFuture<'_, std::io::Result<Vec<String>>> {
+        Box::pin(
+            async move { Err(self.unsupported("list the hosts, set the container to the host")) },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn exec_command_of_the_runtimes() {
+        let config = ProxyConfig {
+            container: "shop-web-1".into(),
+            docker_internal_path: "/usr/src/app".into(),
+            command: "pylsp".into(),
+            user: Some("vscode".into()),
+            env: [("PYTHONPATH".to_string(), "/opt/lib".to_string())].into(),
+            ..Default::default()
+        };
+
+        let (program, args) = PODMAN.exec(&config, &["--stdio".into()]);
+        assert_eq!(program, "podman");
+        assert_eq!(
+            args,
+            [
+                "exec",
+                "-i",
+                "--workdir",
+                "/usr/src/app",
+                "--user",
+                "vscode",
+                "-e",
+                "PYTHONPATH=/opt/lib",
+                "shop-web-1",
+                "pylsp",
+                "--stdio"
+            ]
+        );
+
+        let config = ProxyConfig {
+            runtime: Runtime::Podman,
+            ..config
+        };
+        assert_eq!(runtime(&config).exec(&config, &[]).0, "podman");
+    }
+
+    #[test]
+    fn launcher_template() {
+        let launcher = Launcher {
+            template: vec![
+                "distrobox".into(),
+                "enter".into(),
+                "{container}".into(),
+                "--".into(),
+                "{executable}".into(),
+            ],
+            workdir: "/home/me/dev/shop".into(),
+        };
+        let (program, args) = launcher.command("fedora", "pylsp");
+        assert_eq!(program, "distrobox");
+        assert_eq!(args, ["enter", "fedora", "--", "pylsp"]);
+
+        let launcher = Launcher {
+            template: vec![
+                "nix".into(),
+                "develop".into(),
+                "{workdir}".into(),
+                "-c".into(),
+            ],
+            workdir: "/home/me/dev/shop".into(),
+        };
+        let (program, args) = launcher.command("", "cat");
+        assert_eq!(program, "nix");
+        assert_eq!(args, ["develop", "/home/me/dev/shop", "-c", "cat"]);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn launcher_reads_files() {
+        let dir = temp_dir("launcher_reads_files");
+        let path = dir.join("module.py");
+        std::fs::write(&path, "import os\n").unwrap();
+
+        // `env cat <path>` runs in the host, as a launcher without isolation
+        let launcher = Launcher {
+            template: vec!["env".into(), "{executable}".into()],
+            workdir: String::new(),
+        };
+        let content = launcher
+            .read_file("", path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(content, b"import os\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A `kubectl` that lists the pods and runs the `exec` commands in the host
+    #[cfg(unix)]
+    fn fake_kubectl(dir: &std::path::Path, selector: &str) -> KubernetesCli {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = r#"#!/bin/sh
+case "$*" in
+    *"get pods -n dev -l app=api "*) echo "api-7d9f-x2k4c" ;;
+    *"get pods -n dev -l app=web "*) echo "web-1 web-0" ;;
+    *"get pod -n dev api-7d9f-x2k4c "*) echo "Running" ;;
+    *"get pod -n dev web-0 "*) echo "Pending" ;;
+    "exec -n dev api-7d9f-x2k4c -c lsp -- "*) shift 7; exec "$@" ;;
+    *) echo "Error from server (NotFound)" >&2; exit 1 ;;
+esac
+"#;
+        let path = dir.join("kubectl");
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        KubernetesCli {
+            program: path.to_str().unwrap().into(),
+            kubernetes: Kubernetes {
+                namespace: Some("dev".into()),
+                selector: Some(selector.into()),
+                container: Some("lsp".into()),
+            },
+        }
+    }
+
+    #[test]
+    fn kubernetes_exec_command() {
+        let kubectl = KubernetesCli {
+            program: "kubectl".into(),
+            kubernetes: Kubernetes {
+                namespace: Some("dev".into()),
+                selector: None,
+                container: Some("lsp".into()),
+            },
+        };
+        let config = ProxyConfig {
+            container: "api-7d9f-x2k4c".into(),
+            docker_internal_path: "/workspace".into(),
+            command: "pylsp".into(),
+            env: [("PYTHONPATH".to_string(), "/opt/lib".to_string())].into(),
+            ..Default::default()
+        };
+
+        let (program, args) = kubectl.exec(&config, &["--stdio".into()]);
+        assert_eq!(program, "kubectl");
+        assert_eq!(
+            args,
+            [
+                "exec",
+                "-i",
+                "-n",
+                "dev",
+                "api-7d9f-x2k4c",
+                "-c",
+                "lsp",
+                "--",
+                "sh",
+                "-c",
+                r#"cd "$0" && exec "$@""#,
+                "/workspace",
+                "env",
+                "PYTHONPATH=/opt/lib",
+                "pylsp",
+                "--stdio"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn kubernetes_pods() {
+        let dir = temp_dir("kubernetes_pods");
+        let kubectl = fake_kubectl(&dir, "app=api");
+        assert_eq!(kubectl.select().await, Some(Ok("api-7d9f-x2k4c".into())));
+        assert_eq!(
+            kubectl.status("api-7d9f-x2k4c").await.unwrap(),
+            ContainerStatus::Running
+        );
+        assert_eq!(
+            kubectl.status("api-gone").await.unwrap(),
+            ContainerStatus::NotFound
+        );
+
+        let path = dir.join("module.py");
+        std::fs::write(&path, "import os\n").unwrap();
+        let content = kubectl
+            .read_file("api-7d9f-x2k4c", path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(content, b"import os\n");
+
+        let kubectl = fake_kubectl(&dir, "app=web");
+        assert_eq!(
+            kubectl.select().await,
+            Some(Err(
+                "app=web matches 2 running pods: web-0, web-1; use a more specific selector".into()
+            ))
+        );
+        assert_eq!(
+            kubectl.status("web-0").await.unwrap(),
+            ContainerStatus::Stopped
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ssh_exec_command() {
+        let ssh = SshCli {
+            program: "ssh".into(),
+            user: Some("me".into()),
+        };
+        let config = ProxyConfig {
+            container: "buildbox".into(),
+            docker_internal_path: "/srv/my projects/shop".into(),
+            command: "pylsp".into(),
+            env: [("PYTHONPATH".to_string(), "/opt/lib".to_string())].into(),
+            ..Default::default()
+        };
+
+        let (program, args) = ssh.exec(
+            &config,
+            &["--stdio".into(), "--log-file".into(), "/tmp/my log".into()],
+        );
+        assert_eq!(program, "ssh");
+        assert_eq!(
+            args,
+            [
+                "-T",
+                "-l",
+                "me",
+                "buildbox",
+                "--",
+                "cd '/srv/my projects/shop' && exec env PYTHONPATH=/opt/lib pylsp --stdio --log-file '/tmp/my log'"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn ssh_runs_in_the_host() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The remote shell of the stub is the local one
+        let script = r#"#!/bin/sh
+[ "$1" = "-T" ] && shift
+[ "$1" = "-l" ] && shift 2
+host="$1"
+shift 2
+[ "$host" = "down" ] && { echo "ssh: connect to host down port 22: Connection refused" >&2; exit 255; }
+exec sh -c "$*"
+"#;
+        let dir = temp_dir("ssh_runs_in_the_host");
+        let stub = dir.join("ssh");
+        std::fs::write(&stub, script).unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ssh = SshCli {
+            program: stub.to_str().unwrap().into(),
+            user: None,
+        };
+
+        assert_eq!(
+            ssh.status("buildbox").await.unwrap(),
+            ContainerStatus::Running
+        );
+        let error = ssh.status("down").await.unwrap_err();
+        assert!(error.to_string().contains("Connection refused"));
+
+        let path = dir.join("my module.py");
+        std::fs::write(&path, "import os\n").unwrap();
+        let content = ssh
+            .read_file("buildbox", path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(content, b"import os\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
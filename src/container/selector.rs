@@ -0,0 +1,144 @@
+use std::fmt::Display;
+
+use super::mounts::ContainerInspect;
+
+/// A `container` that selects the running containers by their properties instead of by name
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// `label:key=value`, or `label:key` to only require the label
+    Label { key: String, value: Option<String> },
+    /// `image:repo/name`, the tag is optional
+    Image(String),
+    /// `id:<prefix>`
+    Id(String),
+}
+
+impl Selector {
+    /// Parse the `container` field, returns None for a container name
+    pub fn parse(container: &str) -> Option<Self> {
+        let (kind, value) = container.split_once(':')?;
+        match kind {
+            "label" => {
+                let (key, value) = match value.split_once('=') {
+                    Some((key, value)) => (key, Some(value.to_string())),
+                    None => (value, None),
+                };
+                Some(Self::Label {
+                    key: key.to_string(),
+                    value,
+                })
+            }
+            "image" => Some(Self::Image(value.to_string())),
+            "id" => Some(Self::Id(value.to_string())),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, container: &ContainerInspect) -> bool {
+        match self {
+            Self::Label { key, value } => match (container.label(key), value) {
+                (Some(label), Some(value)) => label == value,
+                (label, None) => label.is_some(),
+                (None, Some(_)) => false,
+            },
+            Self::Image(image) => {
+                let actual = &container.config.image;
+                // Without a tag or digest in the selector, any of them matches
+                actual == image
+                    || actual
+                        .strip_prefix(image.as_str())
+                        .is_some_and(|rest| rest.starts_with([':', '@']))
+            }
+            Self::Id(prefix) => !prefix.is_empty() && container.id.starts_with(prefix.as_str()),
+        }
+    }
+
+    /// Select the only running container that matches. The error describes why none is selected:
+    /// no container matches, or several containers match.
+    pub fn select(&self, containers: &[ContainerInspect]) -> Result<String, String> {
+        let mut matches: Vec<&str> = containers
+            .iter()
+            .filter(|c| self.matches(c))
+            .map(ContainerInspect::name)
+            .collect();
+        matches.sort();
+
+        match matches.as_slice() {
+            [] => Err(format!("no running container matches {self}")),
+            [name] => Ok(name.to_string()),
+            names => Err(format!(
+                "{self} matches {} running containers: {}; use a more specific selector",
+                names.len(),
+                names.join(", ")
+            )),
+        }
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Label { key, value: None } => write!(f, "label:{key}"),
+            Self::Label {
+                key,
+                value: Some(value),
+            } => write!(f, "label:{key}={value}"),
+            Self::Image(image) => write!(f, "image:{image}"),
+            Self::Id(prefix) => write!(f, "id:{prefix}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::fixtures::containers;
+
+    #[test]
+    fn parse_selectors() {
+        assert_eq!(Selector::parse("app-web-1"), None);
+        assert_eq!(
+            Selector::parse("label:dev.team/lsp=python"),
+            Some(Selector::Label {
+                key: "dev.team/lsp".into(),
+                value: Some("python".into())
+            })
+        );
+        assert_eq!(
+            Selector::parse("label:dev.team/lsp").unwrap().to_string(),
+            "label:dev.team/lsp"
+        );
+        assert_eq!(
+            Selector::parse("image:repo/name:tag"),
+            Some(Selector::Image("repo/name:tag".into()))
+        );
+    }
+
+    #[test]
+    fn select_by_selector() {
+        let containers = containers();
+        let select = |s: &str| Selector::parse(s).unwrap().select(&containers);
+
+        assert_eq!(select("label:dev.team/lsp=python").unwrap(), "ci-python-7");
+        assert_eq!(
+            select("image:registry.local/team/python").unwrap(),
+            "ci-python-7"
+        );
+        assert_eq!(
+            select("image:registry.local/team/python:3.12").unwrap(),
+            "ci-python-7"
+        );
+        assert_eq!(select("id:9b7").unwrap(), "ci-node-2");
+
+        assert_eq!(
+            select("label:dev.team/lsp=rust").unwrap_err(),
+            "no running container matches label:dev.team/lsp=rust"
+        );
+        assert_eq!(
+            select("image:registry.local/team/node").unwrap_err(),
+            "image:registry.local/team/node matches 2 running containers: ci-node-2, ci-node-3; use a more specific selector"
+        );
+        assert!(select("image:registry.local/team/py").is_err());
+        assert!(select("id:9b").is_err());
+    }
+}
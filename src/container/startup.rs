@@ -0,0 +1,166 @@
+use tracing::{debug, info, warn};
+
+use crate::config::{ProxyConfig, Source};
+use crate::container::{
+    ContainerStatus, derive_mappings, inspect_container, list_running_containers, runtime,
+    select_compose_container, select_container, select_devcontainer, selector::Selector,
+};
+
+/// Resolve the container values that depend on the running containers. The steps run in
+/// this order: resolve the compose service, the container selector or the dev container;
+/// discover the container that mounts the project; let the runtime select the container, e.g.
+/// the pod of a selector; check that the container is running; derive the mappings from its
+/// mounts. Docker is disabled when any step fails, and `docker_reason` records why.
+pub async fn prepare(config: &mut ProxyConfig) {
+    let runtime = runtime(config);
+    let selector = Selector::parse(&config.container);
+    let labeled =
+        config.compose.is_some() || selector.is_some() || config.devcontainer_folder.is_some();
+
+    // The labels or the selector identify the container, the listed containers are running
+    if config.use_docker && labeled {
+        match list_running_containers(&*runtime).await {
+            Ok(containers) => {
+                let (selected, target) = if let Some(compose) = &config.compose {
+                    let target = format!("compose service {}", compose.service);
+                    (
+                        select_compose_container(compose, &config.local_path, &containers)
+                            .ok_or_else(|| format!("no running container of {target}")),
+                        target,
+                    )
+                } else if let Some(selector) = &selector {
+                    (selector.select(&containers), selector.to_string())
+                } else {
+                    let folder = config.devcontainer_folder.as_deref().unwrap_or_default();
+                    let target = format!("dev container of {folder}");
+                    (
+                        select_devcontainer(folder, &containers)
+                            .ok_or_else(|| format!("no running container of {target}")),
+                        target,
+                    )
+                };
+
+                match selected {
+                    Ok(container) => {
+                        info!(%container, %target, "Container resolved");
+                        config.container = container;
+                        config
+                            .sources
+                            .set("container", Source::Derived(target), None);
+                    }
+                    Err(reason) => {
+                        warn!(%target, %reason, "Container not resolved, falling back to local");
+                        fallback(config, reason);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!(%e, "Failed to list the containers, falling back to local");
+                fallback(config, format!("failed to list the containers: {e}"));
+            }
+        }
+    }
+
+    // Discover the container that mounts the project
+    if config.use_docker && config.auto_container {
+        match list_running_containers(&*runtime).await {
+            Ok(containers) => match select_container(&config.local_path, &containers) {
+                Some(container) => {
+                    info!(%container, "Container discovered");
+                    config.container = container;
+                    config.sources.set(
+                        "container",
+                        Source::Derived("running containers".into()),
+                        None,
+                    );
+                }
+                None => {
+                    warn!(local_path=%config.local_path, "No running container mounts the project, falling back to local");
+                    fallback(config, "no running container mounts the project".into());
+                }
+            },
+            Err(e) => {
+                warn!(%e, "Failed to list the containers, falling back to local");
+                fallback(config, format!("failed to list the containers: {e}"));
+            }
+        }
+    }
+
+    // The runtime selects the container itself, e.g. the pod of the kubernetes selector
+    if config.use_docker
+        && !labeled
+        && config.container.is_empty()
+        && let Some(selected) = runtime.select().await
+    {
+        let target = format!("{} selector", config.runtime);
+        match selected {
+            Ok(container) => {
+                info!(%container, %target, "Container resolved");
+                config.container = container;
+                config
+                    .sources
+                    .set("container", Source::Derived(target), None);
+            }
+            Err(reason) => {
+                warn!(%target, %reason, "Container not resolved, falling back to local");
+                fallback(config, reason);
+            }
+        }
+    }
+
+    // Check if Docker container exists before trying to use it
+    if config.use_docker && !labeled {
+        match runtime.status(&config.container).await {
+            Ok(ContainerStatus::Running) => {
+                debug!(container=%config.container, "Container is running");
+            }
+            Ok(ContainerStatus::Stopped) => {
+                warn!(container=%config.container, "Container exists but is not running, falling back to local");
+                let reason = format!("container {} is not running", config.container);
+                fallback(config, reason);
+            }
+            Ok(ContainerStatus::NotFound) => {
+                warn!(container=%config.container, "Container not found, falling back to local");
+                let reason = format!("container {} not found", config.container);
+                fallback(config, reason);
+            }
+            Err(e) => {
+                warn!(%e, "Failed to check the container runtime, falling back to local");
+                fallback(config, format!("failed to check {}: {e}", config.runtime));
+            }
+        }
+    }
+
+    // Derive the internal path and the mappings from the container mounts
+    if config.use_docker && config.auto_mappings {
+        match inspect_container(&*runtime, &config.container).await {
+            Ok(inspect) => {
+                if derive_mappings(config, &inspect) {
+                    for name in ["docker_internal_path", "mappings"] {
+                        config
+                            .sources
+                            .set(name, Source::Derived("container mounts".into()), None);
+                    }
+                } else {
+                    warn!(container=%config.container, "Could not derive the internal path from the container mounts, falling back to local");
+                    fallback(
+                        config,
+                        "the internal path cannot be derived from the container mounts".into(),
+                    );
+                }
+            }
+            Err(e) => {
+                warn!(%e, "Failed to inspect the container mounts, falling back to local");
+                fallback(
+                    config,
+                    format!("failed to inspect the container mounts: {e}"),
+                );
+            }
+        }
+    }
+}
+
+fn fallback(config: &mut ProxyConfig, reason: String) {
+    config.use_docker = false;
+    config.docker_reason = format!("{}, but {reason}", config.docker_reason);
+}
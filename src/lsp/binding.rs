@@ -1,11 +1,10 @@
-use crate::{config::ProxyConfig, proxy::Pair};
+use crate::{config::ProxyConfig, container::runtime, proxy::Pair};
 use memchr::memmem::{find, find_iter};
 use serde_json::{Value, json};
-use std::{future::Future, path::PathBuf, pin::Pin, process::Stdio, sync::Arc};
+use std::{future::Future, path::PathBuf, pin::Pin, sync::Arc};
 use tokio::{
     fs::{File, create_dir_all},
     io::AsyncWriteExt,
-    process::Command,
 };
 use tokio_util::bytes::Bytes;
 use tracing::{debug, error, trace};
@@ -13,29 +12,44 @@ use tracing::{debug, error, trace};
 use std::collections::HashMap;
 use tokio::sync::RwLock;
 
-/// Redirect the paths from the sender pair to the receiver pair
+/// Redirect the paths from the sender pair to the receiver pair, every mapping is applied
+/// and the longest prefix wins when several of them match at the same position
 pub fn redirect_uri(
     raw_bytes: &mut Bytes,
     from: &Pair,
     config: &ProxyConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let (from_path, to_path): (&[u8], &[u8]) = match from {
-        Pair::Client => (
-            config.local_path.as_bytes(),
-            config.docker_internal_path.as_bytes(),
-        ),
-        Pair::Server => (
-            config.docker_internal_path.as_bytes(),
-            config.local_path.as_bytes(),
-        ),
-    };
+    let mut matches: Vec<(usize, &[u8], &[u8])> = Vec::new();
+
+    for (local, docker) in config.path_mappings() {
+        let (from_path, to_path): (&[u8], &[u8]) = match from {
+            Pair::Client => (local.as_bytes(), docker.as_bytes()),
+            Pair::Server => (docker.as_bytes(), local.as_bytes()),
+        };
 
-    trace!(from=?String::from_utf8_lossy(from_path), to=?String::from_utf8_lossy(to_path));
+        if from_path.is_empty() {
+            continue;
+        }
+
+        trace!(from=?String::from_utf8_lossy(from_path), to=?String::from_utf8_lossy(to_path));
+        matches.extend(find_iter(raw_bytes, from_path).map(|pos| (pos, from_path, to_path)));
+    }
+
+    if matches.is_empty() {
+        return Ok(());
+    }
+
+    // Sort by position and then by the longest prefix
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.len().cmp(&a.1.len())));
 
     let mut new_bytes = Vec::new();
     let mut last = 0;
 
-    for pos in find_iter(raw_bytes, from_path) {
+    for (pos, from_path, to_path) in matches {
+        // Overlaps with a previous replacement
+        if pos < last {
+            continue;
+        }
         new_bytes.extend_from_slice(&raw_bytes[last..pos]);
         new_bytes.extend_from_slice(to_path);
         last = pos + from_path.len();
@@ -215,26 +229,17 @@ impl RequestTracker {
     async fn copy_file(&self, path: &str, destination: &str) -> std::io::Result<()> {
         // Only copy the file if the LSP is in a container
         debug!("Starting file copy from {} to {}", path, destination);
-        let output = Command::new("docker")
-            .args(["exec", &self.config.container, "cat", path])
-            .stdout(Stdio::piped())
-            .stdin(Stdio::null())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("Command failed with status {}: {}", output.status, stderr);
-            return Err(std::io::Error::other(format!("command failed: {}", stderr)));
-        }
+        let content = runtime(&self.config)
+            .read_file(&self.config.container, path)
+            .await
+            .inspect_err(|e| error!("Failed to read the file: {e}"))?;
 
         let mut file = File::create(destination).await?;
-        file.write_all(&output.stdout).await?;
+        file.write_all(&content).await?;
 
         debug!(
             "Successfully wrote {} bytes to {}",
-            output.stdout.len(),
+            content.len(),
             destination
         );
         Ok(())
@@ -260,7 +265,7 @@ pub fn redirect_goto_methods<'a>(
         if let Some(results) = v.get_mut("result").and_then(Value::as_array_mut) {
             for result in results {
                 if let Some(uri_val) = result.get("uri").and_then(|u| u.as_str())
-                    && !uri_val.contains(&tracker.config.local_path)
+                    && !tracker.config.is_local_uri(uri_val)
                 {
                     let new_uri = tracker.bind_library(uri_val).await?;
                     RequestTracker::modify_uri(result, &new_uri);
@@ -304,7 +309,7 @@ pub fn ensure_root(msg: &mut Bytes, config: &ProxyConfig) {
     let key = b"\"workspaceFolders\":[";
     if let Some(beg) = find(msg, key).map(|p| p + key.len())
         && let Some(end) = find(&msg[beg..], b"]").map(|p| p + beg)
-        && let Some(ws) = patch_workspace_folders(&msg[beg..end], &docker_uri, &config.local_path)
+        && let Some(ws) = patch_workspace_folders(&msg[beg..end], &docker_uri, config)
     {
         let before = &msg[..beg];
         let after = &msg[end..];
@@ -312,14 +317,18 @@ pub fn ensure_root(msg: &mut Bytes, config: &ProxyConfig) {
     }
 }
 
-fn patch_workspace_folders(msg: &[u8], docker_uri: &str, local_path: &str) -> Option<Bytes> {
+fn patch_workspace_folders(msg: &[u8], docker_uri: &str, config: &ProxyConfig) -> Option<Bytes> {
     let key = b"\"uri\":\"";
 
     // Check if we have any matches before allocating
     let mut matches = find_iter(msg, key).peekable();
     matches.peek()?;
 
-    let local_path_uri = format!("file://{local_path}");
+    let uri_mappings: Vec<(String, String)> = config
+        .path_mappings()
+        .filter(|(local, _)| !local.is_empty())
+        .map(|(local, docker)| (format!("file://{local}"), format!("file://{docker}")))
+        .collect();
 
     let mut new_bytes = Vec::new();
     // Cursor to track the position of replacements
@@ -332,18 +341,26 @@ fn patch_workspace_folders(msg: &[u8], docker_uri: &str, local_path: &str) -> Op
             // Append everything from the last position up to the start of the URI value
             new_bytes.extend_from_slice(&msg[last_pos..beg]);
 
-            // Force the path to the directory, this occurres in VSCode when in some
-            // cases the workspaceFolders is set to a relative dir like `file://app`
-            new_bytes.extend_from_slice(docker_uri.as_bytes());
-
-            // First try to change the root dir if it is match with the local dir
-            if let Some(pattern_init) =
-                find(&msg[beg..end], local_path_uri.as_bytes()).map(|p| p + beg)
-            {
-                let pattern_end = pattern_init + local_path_uri.len();
-                // Fill the gap, when local path is `/my/local/path` and root is `/my/local/path/subdir`
-                // we add here `/subdir`
-                new_bytes.extend_from_slice(&msg[pattern_end..end]);
+            // First try to change the root dir if it is match with a local dir, the longest
+            // local path wins
+            let mapped = uri_mappings
+                .iter()
+                .filter_map(|(local_uri, docker_uri)| {
+                    find(&msg[beg..end], local_uri.as_bytes())
+                        .map(|p| (p + beg + local_uri.len(), local_uri.len(), docker_uri))
+                })
+                .max_by_key(|(_, len, _)| *len);
+
+            match mapped {
+                Some((pattern_end, _, mapped_uri)) => {
+                    new_bytes.extend_from_slice(mapped_uri.as_bytes());
+                    // Fill the gap, when local path is `/my/local/path` and root is `/my/local/path/subdir`
+                    // we add here `/subdir`
+                    new_bytes.extend_from_slice(&msg[pattern_end..end]);
+                }
+                // Force the path to the directory, this occurres in VSCode when in some
+                // cases the workspaceFolders is set to a relative dir like `file://app`
+                None => new_bytes.extend_from_slice(docker_uri.as_bytes()),
             }
 
             last_pos = end;
@@ -359,6 +376,7 @@ fn patch_workspace_folders(msg: &[u8], docker_uri: &str, local_path: &str) -> Op
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::PathMapping;
     use crate::lsp::parser::lsp_utils::{lspbody, lspmsg};
 
     fn construct_config() -> ProxyConfig {
@@ -551,6 +569,74 @@ mod tests {
             matches
         );
     }
+
+    #[test]
+    fn redirect_uri_with_multiple_mappings() {
+        let mut config = construct_config();
+        config.mappings = vec![
+            PathMapping {
+                local_path: "/test/path/libs".into(),
+                docker_internal_path: "/opt/libs".into(),
+            },
+            PathMapping {
+                local_path: "/home/me/.venv".into(),
+                docker_internal_path: "/usr/local/venv".into(),
+            },
+        ];
+
+        let rq = lspmsg!(
+            "app": "file:///test/path/main.py",
+            "lib": "file:///test/path/libs/shared.py",
+            "venv": "file:///home/me/.venv/lib/site.py"
+        );
+        let ex = lspmsg!(
+            "app": "file:///usr/home/app/main.py",
+            "lib": "file:///opt/libs/shared.py",
+            "venv": "file:///usr/local/venv/lib/site.py"
+        );
+
+        let mut request = Bytes::from(rq.clone());
+        let mut expected = Bytes::from(ex);
+
+        redirect_uri(&mut request, &Pair::Client, &config).unwrap();
+        assert_eq!(
+            lspbody!(&expected => "bytes"),
+            lspbody!(&request => "bytes")
+        );
+
+        redirect_uri(&mut expected, &Pair::Server, &config).unwrap();
+        let request = Bytes::from(rq);
+        assert_eq!(
+            lspbody!(&request => "bytes"),
+            lspbody!(&expected => "bytes")
+        );
+    }
+
+    #[test]
+    fn ensure_root_uses_longest_mapping_for_workspace_folders() {
+        let mut config = construct_config();
+        config.mappings = vec![PathMapping {
+            local_path: "/test/path/libs".into(),
+            docker_internal_path: "/opt/libs".into(),
+        }];
+
+        let input_json = json!({
+            "method": "initialize",
+            "params": {
+                "workspaceFolders": [
+                    { "uri": "file:///test/path/app", "name": "app" },
+                    { "uri": "file:///test/path/libs/shared", "name": "shared" }
+                ]
+            }
+        });
+
+        let mut request = Bytes::from(serde_json::to_vec(&input_json).unwrap());
+        ensure_root(&mut request, &config);
+
+        let body = String::from_utf8(request.to_vec()).unwrap();
+        assert!(body.contains("file:///usr/home/app/app"));
+        assert!(body.contains("file:///opt/libs/shared"));
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader, Chain};
+
+use super::parser::LspFramedReader;
+
+/// A reader that yields the bytes already read and then the rest of the inner reader
+pub type Replay<R> = Chain<Cursor<Vec<u8>>, BufReader<R>>;
+
+/// The `initialize` request, read before spawning the LSP
+#[derive(Debug)]
+pub struct Initialize {
+    pub id: Value,
+    /// Project directory, from `rootUri`, the first entry of `workspaceFolders` or `rootPath`
+    pub root: Option<PathBuf>,
+}
+
+/// Read the messages of the IDE until the `initialize` request. The returned reader replays
+/// every message that was read, so the proxy forwards them to the LSP as if nothing was read.
+/// The request is None when the IDE closes the input before sending it.
+pub async fn wait_initialize<R>(
+    reader: R,
+) -> Result<(Option<Initialize>, Replay<R>), Box<dyn std::error::Error + Send + Sync>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = LspFramedReader::new(reader);
+    let mut replay = vec![];
+    let mut initialize = None;
+
+    while initialize.is_none() {
+        let Some(msgs) = reader.read_messages().await? else {
+            break;
+        };
+
+        for msg in msgs {
+            if initialize.is_none()
+                && let Ok(request) = serde_json::from_slice::<Value>(&msg)
+                && request["method"] == "initialize"
+            {
+                initialize = Some(Initialize {
+                    id: request["id"].clone(),
+                    root: project_root(&request["params"]),
+                });
+            }
+            replay.extend_from_slice(format!("Content-Length: {}\r\n\r\n", msg.len()).as_bytes());
+            replay.extend_from_slice(&msg);
+        }
+    }
+
+    // The bytes of the next message may be read already
+    let (reader, buffer) = reader.into_parts();
+    replay.extend_from_slice(&buffer);
+
+    Ok((initialize, Cursor::new(replay).chain(reader)))
+}
+
+fn project_root(params: &Value) -> Option<PathBuf> {
+    let uri = params["rootUri"]
+        .as_str()
+        .or_else(|| params["workspaceFolders"][0]["uri"].as_str());
+
+    match uri {
+        Some(uri) => uri_to_path(uri),
+        None => params["rootPath"].as_str().map(PathBuf::from),
+    }
+}
+
+/// Path of a `file://` URI, other schemes are not local paths
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    // In Windows the URI is `file:///C:/path`
+    #[cfg(windows)]
+    let path = path.strip_prefix('/').unwrap_or(path);
+
+    Some(PathBuf::from(percent_decode(path)))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    #[tokio::test]
+    async fn wait_and_replay_initialize() {
+        let initialize = frame(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"rootUri":"file:///home/me/dev/my%20shop"}}"#,
+        );
+        let initialized = frame(r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#);
+        let input = format!("{initialize}{initialized}");
+
+        let (request, mut replay) = wait_initialize(input.as_bytes()).await.unwrap();
+        let request = request.unwrap();
+        assert_eq!(request.id, 1);
+        assert_eq!(request.root, Some(PathBuf::from("/home/me/dev/my shop")));
+
+        let mut replayed = String::new();
+        replay.read_to_string(&mut replayed).await.unwrap();
+        assert_eq!(replayed, input);
+    }
+
+    #[test]
+    fn project_root_from_workspace_folders() {
+        let params: Value = serde_json::json!({
+            "rootUri": null,
+            "workspaceFolders": [{ "uri": "file:///srv/api", "name": "api" }],
+        });
+        assert_eq!(project_root(&params), Some(PathBuf::from("/srv/api")));
+
+        let params: Value = serde_json::json!({ "rootPath": "/srv/web" });
+        assert_eq!(project_root(&params), Some(PathBuf::from("/srv/web")));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod binding;
+pub mod initialize;
 pub mod parser;
 pub mod pid;
+pub mod report;
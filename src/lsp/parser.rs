@@ -19,6 +19,11 @@ impl<R: AsyncRead + Unpin> LspFramedReader<R> {
         }
     }
 
+    /// The inner reader and the bytes that were read from it but not parsed yet
+    pub fn into_parts(self) -> (BufReader<R>, BytesMut) {
+        (self.reader, self.buffer)
+    }
+
     /// Read messages from the sender and capture their content. Returns a [`Vec<String>`] with the
     /// messages or None if there are not messages.
     ///
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncRead, AsyncWrite, BufWriter};
+use tokio_util::bytes::Bytes;
+use tracing::debug;
+
+use super::parser::{LspFramedReader, send_message};
+
+/// The IDE sends `initialize` right after spawning the server; when lspdock runs from a terminal
+/// nothing is sent, then do not wait forever
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JSON-RPC internal error
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Report an error that prevents starting the LSP to the IDE, which usually hides the stderr of
+/// the server: wait for the `initialize` request, show the message with `window/showMessage`
+/// and reply the request with an error.
+pub async fn report_startup_error<R, W>(
+    reader: R,
+    writer: W,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = LspFramedReader::new(reader);
+
+    let initialize = async {
+        while let Some(msgs) = reader.read_messages().await? {
+            for msg in msgs {
+                let request: Value = serde_json::from_slice(&msg)?;
+                if request["method"] == "initialize" {
+                    return Ok(Some(request["id"].clone()));
+                }
+            }
+        }
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(None)
+    };
+
+    let id = match tokio::time::timeout(INITIALIZE_TIMEOUT, initialize).await {
+        Ok(Ok(Some(id))) => id,
+        Err(_) | Ok(Ok(None)) | Ok(Err(_)) => {
+            debug!("The initialize request was not received");
+            return Ok(());
+        }
+    };
+
+    reply_startup_error(writer, id, message).await
+}
+
+/// Show the error and reply the `initialize` request `id` with it, when the request was already
+/// read
+pub async fn reply_startup_error<W>(
+    writer: W,
+    id: Value,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut writer = BufWriter::new(writer);
+    let message = format!("lspdock: {message}");
+
+    let show_message = json!({
+        "jsonrpc": "2.0",
+        "method": "window/showMessage",
+        "params": { "type": 1, "message": message },
+    });
+    let response = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": INTERNAL_ERROR, "message": message, "data": { "retry": false } },
+    });
+
+    for msg in [show_message, response] {
+        send_message(&mut writer, &Bytes::from(serde_json::to_vec(&msg)?)).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_initialize_request() {
+        let body = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+        let input = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        let mut output = vec![];
+
+        report_startup_error(input.as_bytes(), &mut output, "unknown key `contaner`")
+            .await
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[tokio::test]
+    async fn report_error_on_initialize() {
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"initialize","params":{}}"#;
+        let input = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        let mut output = vec![];
+
+        report_startup_error(input.as_bytes(), &mut output, "unknown key `contaner`")
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let bodies: Vec<Value> = output
+            .split("Content-Length: ")
+            .filter_map(|frame| frame.split_once("\r\n\r\n"))
+            .map(|(_, body)| serde_json::from_str(body).unwrap())
+            .collect();
+
+        assert_eq!(bodies[0]["method"], "window/showMessage");
+        assert_eq!(
+            bodies[0]["params"]["message"],
+            "lspdock: unknown key `contaner`"
+        );
+        assert_eq!(bodies[1]["id"], 7);
+        assert_eq!(bodies[1]["error"]["code"], INTERNAL_ERROR);
+    }
+}
@@ -1,25 +1,80 @@
+use std::io::IsTerminal;
 use std::process::Stdio;
 use tokio::process::Command;
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 mod config;
+mod container;
 mod lsp;
 mod proxy;
+#[cfg(test)]
+mod test_support;
 
-use tokio::io::{BufReader, BufWriter};
+use tokio::io::{AsyncRead, BufReader, BufWriter};
 
+use lsp::initialize::wait_initialize;
+use lsp::report::{reply_startup_error, report_startup_error};
 use proxy::forward_proxy;
 
-use crate::config::{Cli, ProxyConfig, resolve_config_path};
+use crate::config::{
+    Cli, Commands, ConfigCommand, ProxyConfig, json_schema, render_config, resolve_config_path,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut cli: Cli = Cli::parse();
-    let config_path = resolve_config_path();
-    let mut config = ProxyConfig::from_file(config_path.as_ref(), &mut cli).map_err(|e| {
-        eprintln!("Error retrieving config: {e}");
-        e
-    })?;
+
+    if let Some(Commands::Schema) = cli.command {
+        println!("{}", json_schema());
+        return Ok(());
+    }
+
+    // In lazy mode the project directory is the root of the `initialize` request, the
+    // configuration is resolved from it and the request is replayed to the LSP
+    let mut initialize = None;
+    let mut ide_stdin: Box<dyn AsyncRead + Unpin + Send> = Box::new(tokio::io::stdin());
+    if cli.lazy && cli.command.is_none() {
+        let (request, replay) = wait_initialize(tokio::io::stdin())
+            .await
+            .map_err(|e| format!("Failed to read the initialize request: {e}"))?;
+        if let Some(root) = request.as_ref().and_then(|r| r.root.as_ref())
+            && let Err(e) = std::env::set_current_dir(root)
+        {
+            eprintln!(
+                "Cannot use {} as the project directory: {e}",
+                root.display()
+            );
+        }
+        initialize = request;
+        ide_stdin = Box::new(replay);
+    }
+
+    let config_paths = resolve_config_path(&cli);
+    let mut config = match ProxyConfig::from_file(&config_paths, &mut cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error retrieving config: {e}");
+            // Started by the IDE, show the error there too; from a terminal nobody sends the
+            // initialize request
+            let message = format!("Error retrieving config: {e}");
+            if let Some(request) = initialize {
+                let _ = reply_startup_error(tokio::io::stdout(), request.id, &message).await;
+            } else if cli.command.is_none() && !std::io::stdin().is_terminal() {
+                let _ = report_startup_error(ide_stdin, tokio::io::stdout(), &message).await;
+            }
+            return Err(e.into());
+        }
+    };
+
+    if let Some(Commands::Config {
+        action: ConfigCommand::Show,
+    }) = cli.command
+    {
+        container::prepare(&mut config).await;
+        let (cmd, args) = lsp_command(&config, &cli.args);
+        print!("{}", render_config(&config_paths, &config, &cmd, &args));
+        return Ok(());
+    }
 
     let temp_path;
 
@@ -44,59 +99,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::fmt::layer().with_writer(file_path))
         .init();
 
-    debug!(?config_path);
+    debug!(?config_paths);
     debug!(?config, "configuration file");
+    for warning in &config.warnings {
+        warn!("{warning}");
+    }
 
     info!("Initializing LSP");
-
-    // Check if Docker container exists before trying to use it
-    if config.use_docker {
-        let container_check = Command::new("docker")
-            .args(["inspect", "-f", "{{.State.Running}}", &config.container])
-            .output();
-
-        match container_check.await {
-            Ok(output) if output.status.success() => {
-                let running = String::from_utf8_lossy(&output.stdout).trim() == "true";
-                if !running {
-                    warn!(container=%config.container, "Container exists but is not running, falling back to local");
-                    config.use_docker = false;
-                } else {
-                    debug!(container=%config.container, "Container is running");
-                }
-            }
-            Ok(_) => {
-                warn!(container=%config.container, "Container not found, falling back to local");
-                config.use_docker = false;
-            }
-            Err(e) => {
-                warn!(%e, "Failed to check Docker, falling back to local");
-                config.use_docker = false;
-            }
-        }
+    if let Some(request) = &initialize {
+        info!(root = ?request.root, "Project directory from the initialize request");
     }
+    info!(use_docker = config.use_docker, reason = %config.docker_reason, "Docker decision");
 
-    let (cmd, cmd_args) = if config.use_docker {
-        let cmd = vec![
-            "exec".into(),
-            "-i".into(),
-            "--workdir".into(),
-            config.docker_internal_path.clone(),
-            config.container.clone(),
-            config.executable.clone(),
-        ];
-        ("docker".into(), cmd)
-    } else {
-        (get_fallback_exec(&config), vec![])
-    };
-
-    let mut final_args = cmd_args;
-    final_args.extend(cli.args.clone());
+    container::prepare(&mut config).await;
+    let (cmd, final_args) = lsp_command(&config, &cli.args);
 
     debug!(?cmd, ?final_args, "Spawning LSP");
 
-    let mut child = Command::new(&cmd)
-        .args(&final_args)
+    // The container runtimes pass the environment in the exec command
+    let pass_env = !config.use_docker || container::runtime(&config).process_env();
+
+    let mut command = Command::new(&cmd);
+    command.args(&final_args);
+    if pass_env {
+        command.envs(&config.env);
+    }
+    let mut child = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
@@ -113,19 +141,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Main proxy handler
-    if let Err(e) = forward_proxy(stdin, stdout, config).await {
+    if let Err(e) = forward_proxy(ide_stdin, stdin, stdout, config).await {
         error!("Connection error {e}");
     };
 
     Ok(())
 }
 
+/// Build the program and the arguments that are spawned for the LSP
+fn lsp_command(config: &ProxyConfig, cli_args: &[String]) -> (String, Vec<String>) {
+    // The arguments after `--` have precedence over the server defaults
+    let server_args = if cli_args.is_empty() {
+        &config.args
+    } else {
+        cli_args
+    };
+
+    if config.use_docker {
+        container::runtime(config).exec(config, server_args)
+    } else {
+        (get_fallback_exec(config), server_args.to_vec())
+    }
+}
+
 #[cfg(unix)]
 fn get_fallback_exec(config: &ProxyConfig) -> String {
-    config.executable.clone()
+    config.command.clone()
 }
 
 #[cfg(windows)]
 fn get_fallback_exec(config: &ProxyConfig) -> String {
-    format!("{}.exe", config.executable.clone())
+    format!("{}.exe", config.command.clone())
 }
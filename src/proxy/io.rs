@@ -26,17 +26,21 @@ pub enum Pair {
     Client,
 }
 
-/// Main handler for forwarding and transforming messages between IDE and LSP
-pub async fn forward_proxy<W, R>(
+/// Main handler for forwarding and transforming messages between IDE and LSP. `ide_stdin` is the
+/// input of the proxy, or a reader that replays the messages that were read before spawning
+/// the LSP.
+pub async fn forward_proxy<I, W, R>(
+    ide_stdin: I,
     lsp_stdin: BufWriter<W>,
     lsp_stdout: BufReader<R>,
     mut config: ProxyConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
 where
+    I: AsyncRead + Unpin + Send + 'static,
     W: AsyncWrite + Unpin + Send + 'static,
     R: AsyncRead + Unpin + Send + 'static,
 {
-    let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    let stdin = tokio::io::BufReader::new(ide_stdin);
     let stdout = tokio::io::BufWriter::new(tokio::io::stdout());
 
     let cancel = CancellationToken::new();